@@ -0,0 +1,72 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Data, DataStruct, DeriveInput, Error, Fields, Ident, Result};
+
+pub fn derive(input: &DeriveInput) -> Result<TokenStream> {
+    let Data::Struct(DataStruct {
+        fields: Fields::Named(fields),
+        ..
+    }) = &input.data
+    else {
+        return Err(Error::new_spanned(
+            input,
+            "expected a struct with named fields",
+        ));
+    };
+
+    let (base_fields, fields) = fields.named.iter().partition::<Vec<_>, _>(|field| {
+        field.attrs.iter().any(|attr| attr.path().is_ident("base"))
+    });
+    let base = match base_fields.as_slice() {
+        [base] => base.ident.as_ref().expect("fields are named"),
+        [] => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "expected a `Base<Self>` field marked with `#[base]`",
+            ))
+        }
+        [_, extra, ..] => {
+            return Err(Error::new_spanned(
+                extra,
+                "expected only one `#[base]` field",
+            ));
+        }
+    };
+
+    let name = &input.ident;
+    let vis = &input.vis;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let idents = fields
+        .iter()
+        .map(|field| field.ident.as_ref().expect("fields are named"))
+        .collect::<Vec<_>>();
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let base_arg = Ident::new("base", Span::mixed_site());
+
+    Ok(quote! {
+        impl #impl_generics ::gd_cell::GdClass for #name #ty_generics #where_clause {
+            fn base(&self) -> &::gd_cell::Base<Self> {
+                &self.#base
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Register a new instance with the given fields, and return its id.
+            #vis fn register(#(#idents: #types),*) -> ::gd_cell::InstanceId {
+                ::gd_cell::register_instance_with(|#base_arg| Self {
+                    #base: #base_arg,
+                    #(#idents),*
+                })
+            }
+
+            /// Register a new reference-counted instance with the given fields, and return the only strong
+            /// handle to it.
+            #vis fn register_ref_counted(#(#idents: #types),*) -> ::gd_cell::Instance<Self> {
+                ::gd_cell::register_ref_counted_with(|#base_arg| Self {
+                    #base: #base_arg,
+                    #(#idents),*
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,54 @@
+//! Macros for `gd-cell`, re-exported by it with the `derive` feature.
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, Error, ItemImpl};
+
+mod class;
+mod methods;
+
+/// Implement `GdClass` for a struct with named fields, one of which is a `Base<Self>` marked with `#[base]`.
+///
+/// This also generates two associated functions, which take every field but the base as arguments, in
+/// declaration order:
+///
+/// - `register`, which registers a new instance with `register_instance_with` and returns its id.
+/// - `register_ref_counted`, which registers a new instance with `register_ref_counted_with` and returns the
+///   strong handle to it.
+#[proc_macro_derive(GdClass, attributes(base))]
+pub fn derive_gd_class(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    class::derive(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Generate a function calling each `&self` or `&mut self` method of an impl block on an instance in the
+/// registry, by its id.
+///
+/// For a method `name`, this generates an associated function `call_name`, which takes the id of the instance
+/// followed by the arguments of the method. It borrows the instance like the method's receiver with
+/// `call_ref` or `call_mut`, and returns the result of the method or a `CallErr`.
+///
+/// Methods taking `self` by value, `async` and `unsafe` methods are skipped, as are methods whose return type
+/// contains a reference or a lifetime other than `'static`, since their result may borrow from the instance.
+/// Lifetimes elided in a path, as in `std::slice::Iter<String>`, cannot be told apart from types without
+/// lifetimes and fail to compile. Write them out as `Iter<'_, String>`, or mark the method with
+/// `#[gd_methods(skip)]` to not generate a function for it. Generating `call_name` fails if the impl block
+/// already has a method of that name.
+#[proc_macro_attribute]
+pub fn gd_methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return Error::new(
+            proc_macro2::Span::call_site(),
+            "`gd_methods` does not take arguments",
+        )
+        .into_compile_error()
+        .into();
+    }
+    let item = parse_macro_input!(item as ItemImpl);
+
+    methods::expand(item)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
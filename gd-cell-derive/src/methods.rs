@@ -0,0 +1,165 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    Attribute, Error, FnArg, Ident, ImplItem, ImplItemFn, ItemImpl, Result, ReturnType, Type,
+};
+
+pub fn expand(mut item: ItemImpl) -> Result<TokenStream> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(Error::new_spanned(
+            path,
+            "expected an inherent impl block, not a trait impl",
+        ));
+    }
+
+    let mut methods = Vec::new();
+    for impl_item in &mut item.items {
+        if let ImplItem::Fn(method) = impl_item {
+            let skip = take_skip(&mut method.attrs)?;
+            methods.push((&*method, skip));
+        }
+    }
+
+    let mut wrappers = Vec::new();
+    let mut errors = Vec::<Error>::new();
+    for &(method, skip) in &methods {
+        let Some(wrapper) = wrapper(method).filter(|_| !skip) else {
+            continue;
+        };
+
+        let name = &method.sig.ident;
+        let clash = methods
+            .iter()
+            .find(|(other, _)| other.sig.ident == format_ident!("call_{}", name));
+        match clash {
+            Some((other, _)) => errors.push(Error::new_spanned(
+                &other.sig.ident,
+                format!(
+                    "`call_{name}` clashes with the function `gd_methods` generates for `{name}`, rename it or \
+                     mark `{name}` with `#[gd_methods(skip)]`"
+                ),
+            )),
+            None => wrappers.push(wrapper),
+        }
+    }
+    if let Some(error) = errors.into_iter().reduce(|mut all, error| {
+        all.combine(error);
+        all
+    }) {
+        return Err(error);
+    }
+
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+
+    Ok(quote! {
+        #item
+
+        impl #impl_generics #self_ty #where_clause {
+            #(#wrappers)*
+        }
+    })
+}
+
+/// Removes the `#[gd_methods(skip)]` attributes from `attrs`, and returns `true` if there were any.
+fn take_skip(attrs: &mut Vec<Attribute>) -> Result<bool> {
+    let mut skip = false;
+    let mut result = Ok(());
+
+    attrs.retain(|attr| {
+        if !attr.path().is_ident("gd_methods") {
+            return true;
+        }
+        match attr.parse_args::<Ident>() {
+            Ok(arg) if arg == "skip" => skip = true,
+            _ => {
+                result = Err(Error::new_spanned(
+                    attr,
+                    "expected `#[gd_methods(skip)]` on a method",
+                ))
+            }
+        }
+        false
+    });
+
+    result.map(|()| skip)
+}
+
+/// Returns the function calling `method` by the id of an instance, if `method` borrows `self` and its result
+/// does not.
+fn wrapper(method: &ImplItemFn) -> Option<TokenStream> {
+    let sig = &method.sig;
+    if sig.asyncness.is_some() || sig.unsafety.is_some() {
+        return None;
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        if borrows(ty.to_token_stream()) {
+            return None;
+        }
+    }
+
+    let Type::Reference(receiver) = &*sig.receiver()?.ty else {
+        return None;
+    };
+    let (call, this) = match receiver.mutability {
+        Some(_) => (quote!(::gd_cell::call_mut), quote!(&mut Self)),
+        None => (quote!(::gd_cell::call_ref), quote!(&Self)),
+    };
+
+    let types = sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(arg) => Some(&arg.ty),
+            FnArg::Receiver(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let args = (0..types.len())
+        .map(|i| format_ident!("arg{i}"))
+        .collect::<Vec<_>>();
+
+    let output = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+
+    let vis = &method.vis;
+    let name = &sig.ident;
+    let wrapper = format_ident!("call_{}", name);
+    let (generics, where_clause) = (&sig.generics, &sig.generics.where_clause);
+    let doc = format!("Call [`Self::{name}`] on the instance with id `id`.");
+
+    Some(quote! {
+        #[doc = #doc]
+        #[track_caller]
+        #vis fn #wrapper #generics(
+            id: ::gd_cell::InstanceId,
+            #(#args: #types),*
+        ) -> ::core::result::Result<#output, ::gd_cell::CallErr> #where_clause {
+            #call(id, |this: #this, (#(#args,)*)| this.#name(#(#args),*), (#(#args,)*))
+        }
+    })
+}
+
+/// Returns `true` if `tokens` contain a reference or a lifetime other than `'static`, so a type made of them
+/// may borrow from the receiver.
+fn borrows(tokens: TokenStream) -> bool {
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(group) if borrows(group.stream()) => return true,
+            TokenTree::Punct(punct) if punct.as_char() == '&' => match tokens.peek() {
+                Some(TokenTree::Punct(next)) if next.as_char() == '\'' => {}
+                _ => return true,
+            },
+            TokenTree::Punct(punct) if punct.as_char() == '\'' => match tokens.next() {
+                Some(TokenTree::Ident(name)) if name == "static" => {}
+                _ => return true,
+            },
+            _ => {}
+        }
+    }
+
+    false
+}
@@ -0,0 +1,59 @@
+use gd_cell::{instance, Base, GdClass};
+
+#[derive(GdClass)]
+struct Player {
+    name: &'static str,
+    #[base]
+    base: Base<Player>,
+    health: u32,
+}
+
+impl Player {
+    fn damage(&mut self, amount: u32) {
+        self.health = self.health.saturating_sub(amount);
+    }
+
+    fn damage_twice(&mut self, amount: u32) {
+        self.damage(amount);
+        self.base_mut()
+            .unwrap()
+            .call_mut(|this| this.damage(amount))
+            .unwrap();
+    }
+}
+
+#[test]
+fn derived_class_registers_and_reenters() {
+    let id = Player::register("hero", 10);
+    let player = instance::<Player>(id).unwrap();
+    assert_eq!(player.bind().unwrap().base().id(), id);
+
+    player.bind_mut().unwrap().damage_twice(3);
+    let bound = player.bind().unwrap();
+    assert_eq!((bound.name, bound.health), ("hero", 4));
+}
+
+#[test]
+fn derived_class_registers_ref_counted() {
+    let player = Player::register_ref_counted("villain", 5);
+    let weak = player.downgrade();
+    assert_eq!(player.bind().unwrap().base.to_weak().id(), weak.id());
+
+    drop(player);
+    assert!(weak.upgrade().is_none());
+}
+
+#[derive(GdClass)]
+struct Camp {
+    #[base]
+    owner: Base<Camp>,
+    base: &'static str,
+}
+
+#[test]
+fn fields_named_like_the_base_argument_are_kept() {
+    let camp = Camp::register_ref_counted("north");
+    let bound = camp.bind().unwrap();
+    assert_eq!(bound.base, "north");
+    assert_eq!(bound.owner.to_weak().id(), camp.id());
+}
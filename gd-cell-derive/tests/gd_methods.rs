@@ -0,0 +1,80 @@
+use gd_cell::{gd_methods, Base, BorrowStateErr, CallErr, GdClass};
+
+#[derive(GdClass)]
+struct Inventory {
+    #[base]
+    base: Base<Inventory>,
+    items: Vec<String>,
+}
+
+#[gd_methods]
+impl Inventory {
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn add(&mut self, item: &str, count: usize) {
+        self.items.extend((0..count).map(|_| item.to_owned()));
+    }
+
+    fn count_of(&self, item: &str) -> usize {
+        self.items.iter().filter(|held| *held == item).count()
+    }
+
+    fn add_while_counting(&mut self, item: &str) -> Result<(), CallErr> {
+        // Reentering without marking the borrow as non-aliasing conflicts with it.
+        Inventory::call_count_of(self.base().id(), item).map(|_| ())
+    }
+
+    fn take_items(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.items)
+    }
+
+    // Getters returning borrows are skipped, so they can stay in the same impl block.
+    fn first(&self) -> Option<&str> {
+        self.items.first().map(String::as_str)
+    }
+
+    fn kind(&self) -> &'static str {
+        "inventory"
+    }
+
+    // The elided lifetime cannot be seen, so the method needs to be skipped explicitly.
+    #[gd_methods(skip)]
+    #[allow(mismatched_lifetime_syntaxes)]
+    fn iter(&self) -> std::slice::Iter<String> {
+        self.items.iter()
+    }
+
+    fn iter_len(&self) -> usize {
+        self.iter().len()
+    }
+}
+
+#[test]
+fn generated_wrappers_bind_by_receiver() {
+    let id = Inventory::register(Vec::new());
+
+    Inventory::call_add(id, "potion", 2).unwrap();
+    Inventory::call_add(id, "sword", 1).unwrap();
+    assert_eq!(Inventory::call_len(id).unwrap(), 3);
+    assert_eq!(Inventory::call_iter_len(id).unwrap(), 3);
+    assert_eq!(Inventory::call_count_of(id, "potion").unwrap(), 2);
+
+    let err = Inventory::call_add_while_counting(id, "sword").unwrap();
+    let Err(CallErr::Borrow(err)) = err else {
+        panic!("expected a borrow error, got {err:?}");
+    };
+    assert_eq!(err.error, BorrowStateErr::HasAliasingRef);
+
+    assert_eq!(Inventory::call_kind(id).unwrap(), "inventory");
+    let first = gd_cell::call_ref(
+        id,
+        |this: &Inventory, ()| this.first().map(str::to_owned),
+        (),
+    );
+    assert_eq!(first.unwrap().as_deref(), Some("potion"));
+    assert_eq!(Inventory::call_take_items(id).unwrap().len(), 3);
+    assert!(gd_cell::free_instance(id));
+    assert!(matches!(Inventory::call_len(id), Err(CallErr::Instance(_))));
+}
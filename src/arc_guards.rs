@@ -0,0 +1,201 @@
+//! Guards which keep their [`GdCell`] alive through an [`Arc`], so they are `'static` and can be stored or
+//! moved freely.
+
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    error::Error,
+    fmt,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    ptr,
+};
+
+use crate::guards::{GdMut, GdRef};
+use crate::lock::{DefaultLockFamily, LockFamily};
+use crate::{GdCell, DEFAULT_REENTRANCY_DEPTH};
+
+/// Extend the lifetime of a borrow of `cell` to `'a`.
+///
+/// # Safety
+///
+/// The returned reference must not be used after the last clone of `cell` is dropped.
+unsafe fn extend_lifetime<'a, T, const DEPTH: usize, L: LockFamily>(
+    cell: &Pin<Arc<GdCell<T, DEPTH, L>>>,
+) -> Pin<&'a GdCell<T, DEPTH, L>> {
+    // SAFETY:
+    // The pointer comes from a live `Arc`, and is valid for as long as the caller ensures. The cell is pinned
+    // because it is behind a `Pin<Arc<_>>`.
+    unsafe { Pin::new_unchecked(&*ptr::from_ref(&**cell)) }
+}
+
+/// A shared borrow of a [`GdCell`] which owns a reference count of the cell, see
+/// [`GdCell::gd_ref_arc`].
+pub struct ArcGdRef<
+    T,
+    const DEPTH: usize = DEFAULT_REENTRANCY_DEPTH,
+    L: LockFamily = DefaultLockFamily,
+> {
+    // Declared before `cell`, so it is dropped while the cell is still alive.
+    guard: GdRef<'static, T, L>,
+    cell: Pin<Arc<GdCell<T, DEPTH, L>>>,
+}
+
+impl<T: 'static, const DEPTH: usize, L: LockFamily> ArcGdRef<T, DEPTH, L> {
+    #[track_caller]
+    pub(crate) fn new(cell: &Pin<Arc<GdCell<T, DEPTH, L>>>) -> Result<Self, Box<dyn Error>> {
+        // SAFETY:
+        // The guard is dropped before the clone of `cell` stored next to it.
+        let guard = unsafe { extend_lifetime(cell) }.gd_ref()?;
+        // The guard may be moved to another thread, so the borrow is not held by this one.
+        #[cfg(feature = "deadlock-detection")]
+        crate::deadlock::disown(cell.address());
+
+        Ok(Self {
+            guard,
+            cell: Pin::clone(cell),
+        })
+    }
+
+    /// Returns the cell this guard borrows.
+    pub fn cell(&self) -> &Pin<Arc<GdCell<T, DEPTH, L>>> {
+        &self.cell
+    }
+}
+
+impl<T, const DEPTH: usize, L: LockFamily> Deref for ArcGdRef<T, DEPTH, L> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+/// A mutable borrow of a [`GdCell`] which owns a reference count of the cell, see
+/// [`GdCell::gd_mut_arc`].
+pub struct ArcGdMut<
+    T,
+    const DEPTH: usize = DEFAULT_REENTRANCY_DEPTH,
+    L: LockFamily = DefaultLockFamily,
+> {
+    // Declared before `cell`, so it is dropped while the cell is still alive.
+    guard: GdMut<'static, T, L>,
+    cell: Pin<Arc<GdCell<T, DEPTH, L>>>,
+}
+
+impl<T: 'static, const DEPTH: usize, L: LockFamily> ArcGdMut<T, DEPTH, L> {
+    #[track_caller]
+    pub(crate) fn new(cell: &Pin<Arc<GdCell<T, DEPTH, L>>>) -> Result<Self, Box<dyn Error>> {
+        // SAFETY:
+        // The guard is dropped before the clone of `cell` stored next to it.
+        let guard = unsafe { extend_lifetime(cell) }.gd_mut()?;
+        // The guard may be moved to another thread, so the borrow is not held by this one.
+        #[cfg(feature = "deadlock-detection")]
+        crate::deadlock::disown(cell.address());
+
+        Ok(Self {
+            guard,
+            cell: Pin::clone(cell),
+        })
+    }
+
+    /// Returns the cell this guard borrows.
+    ///
+    /// This can be used to mark the borrow as non-aliasing with [`GdCell::set_non_aliasing`].
+    pub fn cell(&self) -> &Pin<Arc<GdCell<T, DEPTH, L>>> {
+        &self.cell
+    }
+}
+
+impl<T, const DEPTH: usize, L: LockFamily> Deref for ArcGdMut<T, DEPTH, L> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T, const DEPTH: usize, L: LockFamily> DerefMut for ArcGdMut<T, DEPTH, L> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+// SAFETY:
+// Moving a guard to another thread hands the `&T` or `&mut T` to that thread, and it shares the cell with the
+// thread that borrowed it. This is what `GdCell` being `Sync` allows.
+#[cfg(feature = "std")]
+unsafe impl<T: Send + Sync, const DEPTH: usize, L: LockFamily> Send for ArcGdRef<T, DEPTH, L> {}
+#[cfg(feature = "std")]
+unsafe impl<T: Send + Sync, const DEPTH: usize, L: LockFamily> Sync for ArcGdRef<T, DEPTH, L> {}
+#[cfg(feature = "std")]
+unsafe impl<T: Send + Sync, const DEPTH: usize, L: LockFamily> Send for ArcGdMut<T, DEPTH, L> {}
+#[cfg(feature = "std")]
+unsafe impl<T: Send + Sync, const DEPTH: usize, L: LockFamily> Sync for ArcGdMut<T, DEPTH, L> {}
+
+/// Forward `Debug` and `Display` of an arc guard to the borrowed value.
+macro_rules! impl_fmt_for_arc_guard {
+    ($($guard:ident),*) => {$(
+        impl<T: fmt::Debug, const DEPTH: usize, L: LockFamily> fmt::Debug for $guard<T, DEPTH, L> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&**self, f)
+            }
+        }
+
+        impl<T: fmt::Display, const DEPTH: usize, L: LockFamily> fmt::Display for $guard<T, DEPTH, L> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&**self, f)
+            }
+        }
+    )*};
+}
+
+impl_fmt_for_arc_guard!(ArcGdRef, ArcGdMut);
+
+#[cfg(test)]
+mod test {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    struct Inventory {
+        items: Vec<&'static str>,
+        // A guard can be stored next to other data, since it does not borrow anything.
+        _keep_bound: Option<ArcGdRef<i32>>,
+    }
+
+    #[test]
+    fn arc_guards_outlive_local_borrows() {
+        let cell = Arc::pin(GdCell::new(Vec::new()));
+        let counter = Arc::pin(GdCell::new(0));
+
+        let mut guard = cell.gd_mut_arc().unwrap();
+        guard.push("sword");
+        let inventory = Inventory {
+            items: core::mem::take(&mut *guard),
+            _keep_bound: Some(counter.gd_ref_arc().unwrap()),
+        };
+        assert!(cell.as_ref().gd_ref().is_err());
+        drop(guard);
+        assert_eq!(inventory.items, ["sword"]);
+
+        assert!(counter.as_ref().gd_mut().is_err());
+        drop(inventory);
+        assert!(counter.as_ref().gd_mut().is_ok());
+
+        let shared = cell.gd_ref_arc().unwrap();
+        drop(cell);
+        assert!(shared.is_empty());
+        assert!(shared.cell().as_ref().gd_mut().is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn arc_guard_moves_to_another_thread() {
+        let cell = Arc::pin(GdCell::new(1));
+
+        let mut guard = cell.gd_mut_arc().unwrap();
+        std::thread::spawn(move || *guard += 1).join().unwrap();
+
+        assert_eq!(*cell.as_ref().gd_ref().unwrap(), 2);
+    }
+}
@@ -0,0 +1,153 @@
+//! Letting a method of an instance in the registry call back into the same instance.
+
+use alloc::boxed::Box;
+use core::{error::Error, fmt, marker::PhantomData, pin::Pin, ptr};
+
+use crate::guards::NonAliasingGuard;
+use crate::registry::{try_instance, Instance, InstanceId, WeakInstance};
+use crate::GdCell;
+
+/// The component of an instance in the registry which knows the id of the instance, see
+/// [`register_instance_with`](crate::register_instance_with).
+///
+/// It does not keep the instance alive.
+pub struct Base<T> {
+    id: InstanceId,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Base<T> {
+    pub(crate) fn new(id: InstanceId) -> Self {
+        Self {
+            id,
+            _type: PhantomData,
+        }
+    }
+
+    /// Returns the id of the instance.
+    pub fn id(&self) -> InstanceId {
+        self.id
+    }
+
+    /// Returns a weak handle to the instance.
+    pub fn to_weak(&self) -> WeakInstance<T> {
+        self.id.downgrade()
+    }
+}
+
+impl<T> fmt::Debug for Base<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Base").field(&self.id).finish()
+    }
+}
+
+/// A type stored in the registry which holds its own [`Base`].
+///
+/// With the `derive` feature, this can be derived for a struct with a `#[base]` field, which also generates
+/// functions to register new instances.
+pub trait GdClass: Sized + Send + Sync + 'static {
+    /// Returns the base of this instance.
+    fn base(&self) -> &Base<Self>;
+
+    /// Allow calling back into this instance while it is mutably borrowed, see [`BaseGuard`].
+    ///
+    /// Fails if the instance was freed, or if `self` is not the currently borrowed value of the instance.
+    #[track_caller]
+    fn base_mut(&mut self) -> Result<BaseGuard<'_, Self>, Box<dyn Error>> {
+        BaseGuard::new(self)
+    }
+}
+
+/// A guard which marks the mutable borrow of an instance as non-aliasing, so methods of the instance can be
+/// called through it, see [`GdClass::base_mut`].
+///
+/// This borrows the `&mut self` it was created from, so the instance can only be accessed through the guard
+/// while it is alive.
+pub struct BaseGuard<'a, T: 'a> {
+    // Declared before `instance`, so it is dropped while the cell is still alive.
+    _non_aliasing_guard: NonAliasingGuard<'a, T>,
+    instance: Instance<T>,
+}
+
+impl<'a, T: GdClass> BaseGuard<'a, T> {
+    #[track_caller]
+    fn new(this: &'a mut T) -> Result<Self, Box<dyn Error>> {
+        let instance = try_instance::<T>(this.base().id())?;
+
+        // SAFETY:
+        // The cell is kept alive by `instance`, which is stored next to the guard and dropped after it. The cell
+        // is pinned because the registry never moves it.
+        let cell: Pin<&'a GdCell<T>> =
+            unsafe { Pin::new_unchecked(&*ptr::from_ref(instance.cell().get_ref())) };
+        let non_aliasing_guard = cell.set_non_aliasing(this)?;
+
+        Ok(Self {
+            _non_aliasing_guard: non_aliasing_guard,
+            instance,
+        })
+    }
+
+    /// Returns the instance this guard refers to.
+    pub fn instance(&self) -> &Instance<T> {
+        &self.instance
+    }
+
+    /// Call `f` with a shared borrow of the instance.
+    #[track_caller]
+    pub fn call_ref<R>(&self, f: impl FnOnce(&T) -> R) -> Result<R, Box<dyn Error>> {
+        Ok(f(&*self.instance.bind()?))
+    }
+
+    /// Call `f` with a new mutable borrow of the instance.
+    #[track_caller]
+    pub fn call_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, Box<dyn Error>> {
+        Ok(f(&mut *self.instance.bind_mut()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::register_instance_with;
+
+    struct Counter {
+        base: Base<Counter>,
+        count: u32,
+    }
+
+    impl GdClass for Counter {
+        fn base(&self) -> &Base<Self> {
+            &self.base
+        }
+    }
+
+    impl Counter {
+        fn increment(&mut self) {
+            self.count += 1;
+        }
+
+        fn increment_twice(&mut self) {
+            self.increment();
+            let base = self.base_mut().unwrap();
+            base.call_mut(Self::increment).unwrap();
+            assert_eq!(base.call_ref(|this| this.count).unwrap(), 2);
+        }
+    }
+
+    #[test]
+    fn base_reenters_instance() {
+        let id = register_instance_with(|base| Counter { base, count: 0 });
+        let instance = try_instance::<Counter>(id).unwrap();
+
+        instance.bind_mut().unwrap().increment_twice();
+        assert_eq!(instance.bind().unwrap().count, 2);
+
+        // Not the value of the instance.
+        let mut copy = Counter {
+            base: Base::new(id),
+            count: 0,
+        };
+        let _guard = instance.bind_mut().unwrap();
+        assert!(copy.base_mut().is_err());
+    }
+}
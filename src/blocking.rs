@@ -0,0 +1,132 @@
+//! Waiting for a [`GdCell`](crate::GdCell) to become available, used by blocking borrows such as
+//! [`GdCell::gd_mut_blocking`](crate::GdCell::gd_mut_blocking).
+//!
+//! Waiting threads are woken whenever a borrow of any cell is released, and then retry their borrow.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use crate::borrow_err::Failure;
+#[cfg(feature = "deadlock-detection")]
+use crate::deadlock;
+
+/// The number of threads currently waiting for any cell.
+static WAITERS: AtomicUsize = AtomicUsize::new(0);
+/// The number of times a borrow was released while threads were waiting.
+static RELEASES: Mutex<u64> = Mutex::new(0);
+static RELEASED: Condvar = Condvar::new();
+
+/// Wake every waiting thread, so they can retry their borrow.
+///
+/// Must be called after a borrow is released and the state lock of its cell is unlocked.
+pub fn notify_released() {
+    // A waiter registers itself before attempting its borrow, and a borrow is released under the same
+    // state lock the attempt takes. So either the attempt saw the release, or we see the waiter.
+    if WAITERS.load(Ordering::SeqCst) > 0 {
+        *RELEASES.lock().unwrap() += 1;
+        RELEASED.notify_all();
+    }
+}
+
+/// Take a borrow of the cell at address `cell` with `attempt`, waiting and retrying for as long as it fails
+/// with an error which another thread may resolve by releasing its borrows.
+#[cfg_attr(not(feature = "deadlock-detection"), allow(unused_variables))]
+pub fn wait_for<R>(
+    cell: usize,
+    mut attempt: impl FnMut() -> Result<R, Failure>,
+) -> Result<R, Failure> {
+    let _waiter = Waiter::register();
+
+    loop {
+        let seen = *RELEASES.lock().unwrap();
+
+        let failure = match attempt() {
+            Err(failure) if failure.error.would_block() => failure,
+            result => return result,
+        };
+
+        // A deadlock is reported with the borrows the current thread would have waited for.
+        #[cfg(feature = "deadlock-detection")]
+        deadlock::start_waiting(cell).map_err(|error| Failure {
+            error,
+            status: failure.status,
+        })?;
+
+        let mut releases = RELEASES.lock().unwrap();
+        while *releases == seen {
+            releases = RELEASED.wait(releases).unwrap();
+        }
+    }
+}
+
+/// Registers the current thread as waiting for as long as it exists.
+struct Waiter;
+
+impl Waiter {
+    fn register() -> Self {
+        WAITERS.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        WAITERS.fetch_sub(1, Ordering::SeqCst);
+
+        #[cfg(feature = "deadlock-detection")]
+        deadlock::stop_waiting();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::pin::pin;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use crate::GdCell;
+
+    #[test]
+    fn blocking_borrow_waits_for_release() {
+        let cell = pin!(GdCell::new(0));
+        let cell = cell.into_ref();
+
+        let (locked, wait_locked) = mpsc::channel();
+        thread::scope(|scope| {
+            let mut guard = cell.gd_mut().unwrap();
+
+            let waiter = scope.spawn(move || {
+                locked.send(()).unwrap();
+                *cell.gd_mut_blocking().unwrap() += 1;
+                *cell.gd_ref_blocking().unwrap()
+            });
+
+            wait_locked.recv().unwrap();
+            *guard += 10;
+            drop(guard);
+
+            assert_eq!(waiter.join().unwrap(), 11);
+        });
+    }
+
+    #[test]
+    fn other_threads_cannot_reenter() {
+        let cell = pin!(GdCell::new(0));
+        let cell = cell.into_ref();
+
+        let mut guard = cell.gd_mut().unwrap();
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard).unwrap();
+
+        thread::scope(|scope| {
+            let err = scope
+                .spawn(|| cell.gd_ref().map(|_| ()).unwrap_err().to_string())
+                .join()
+                .unwrap();
+            assert!(err.contains("another thread"), "{err}");
+        });
+
+        assert!(cell.gd_ref().is_ok());
+        drop(no_alias_guard);
+        drop(guard);
+    }
+}
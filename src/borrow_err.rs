@@ -0,0 +1,238 @@
+use core::{error::Error, fmt, panic::Location};
+
+use crate::borrow_state::BorrowStateErr;
+use crate::error_handler;
+use crate::hooks::BorrowEventKind;
+use crate::status::BorrowStatus;
+
+/// The error returned when borrowing a [`GdCell`](crate::GdCell) fails.
+///
+/// This wraps the [`BorrowStateErr`] the borrow failed with, together with the type stored in the cell and
+/// the borrows that were live at the time. It is displayed like
+/// ``cannot bind `Player` mutably: 2 shared borrows live``.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BorrowErr {
+    /// The borrow which was attempted.
+    pub kind: BorrowEventKind,
+    /// The name of the type stored in the cell.
+    pub type_name: &'static str,
+    /// The borrows of the cell when the borrow failed.
+    pub status: BorrowStatus,
+    /// Why the borrow failed.
+    pub error: BorrowStateErr,
+}
+
+impl BorrowErr {
+    /// Returns `true` if `error` is explained by the live borrows alone.
+    fn is_explained_by_status(&self) -> bool {
+        matches!(
+            self.error,
+            BorrowStateErr::HasSharedRef
+                | BorrowStateErr::HasUpgradableRef
+                | BorrowStateErr::HasMutRef
+                | BorrowStateErr::HasAliasingRef
+        )
+    }
+}
+
+impl fmt::Display for BorrowErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BorrowEventKind as Kind;
+
+        let name = ShortTypeName(self.type_name);
+        match self.kind {
+            Kind::Shared => write!(f, "cannot bind `{name}`")?,
+            Kind::Upgradable => write!(f, "cannot bind `{name}` upgradably")?,
+            Kind::Upgrade => write!(f, "cannot upgrade the binding of `{name}`")?,
+            Kind::Mut => write!(f, "cannot bind `{name}` mutably")?,
+            Kind::SetNonAliasing | Kind::SetNonAliasingReadOnly => write!(
+                f,
+                "cannot mark the mutable binding of `{name}` as non-aliasing"
+            )?,
+            kind => write!(f, "cannot {kind:?} `{name}`")?,
+        }
+
+        if self.is_explained_by_status() {
+            write!(f, ": {}", LiveBorrows(&self.status))
+        } else {
+            write!(f, ": {} ({})", self.error, LiveBorrows(&self.status))
+        }
+    }
+}
+
+impl Error for BorrowErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// A failed borrow, with the borrows of the cell when it failed.
+///
+/// The status is taken while the state of the cell is still locked, so it shows the borrows which caused the
+/// failure rather than the borrows live once the failure is reported.
+#[derive(Debug)]
+pub struct Failure {
+    pub error: BorrowStateErr,
+    pub status: BorrowStatus,
+}
+
+impl Failure {
+    /// Report the failure to the borrow error handler as a failed borrow of `kind` at `location`, of a cell
+    /// storing a `T`, and return the error describing it.
+    pub fn report<T>(
+        self,
+        kind: BorrowEventKind,
+        location: &'static Location<'static>,
+    ) -> BorrowErr {
+        error_handler::report::<T>(&self.error, location);
+
+        BorrowErr {
+            kind,
+            type_name: core::any::type_name::<T>(),
+            status: self.status,
+            error: self.error,
+        }
+    }
+
+    /// Attribute the error to the cell at `address`, see [`BorrowStateErr::in_cell`].
+    pub fn in_cell(self, address: usize) -> Self {
+        Self {
+            error: self.error.in_cell(address),
+            ..self
+        }
+    }
+}
+
+/// Displays a type name without module paths, so `alloc::vec::Vec<game::Player>` becomes `Vec<Player>`.
+struct ShortTypeName(&'static str);
+
+impl fmt::Display for ShortTypeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.0;
+
+        while !rest.is_empty() {
+            let segment_len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let (segment, after) = rest.split_at(segment_len);
+
+            match after.strip_prefix("::") {
+                // `segment` is a module path, skip it.
+                Some(after) => rest = after,
+                None => {
+                    f.write_str(segment)?;
+                    let mut chars = after.chars();
+                    if let Some(c) = chars.next() {
+                        write!(f, "{c}")?;
+                    }
+                    rest = chars.as_str();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Displays the live borrows of a status, like `2 shared borrows live`.
+struct LiveBorrows<'a>(&'a BorrowStatus);
+
+impl fmt::Display for LiveBorrows<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn plural(count: usize) -> &'static str {
+            if count == 1 {
+                ""
+            } else {
+                "s"
+            }
+        }
+
+        let status = self.0;
+        if !status.is_bound() {
+            return write!(f, "no borrows live");
+        }
+
+        if status.shared_count > 0 {
+            let count = status.shared_count;
+            write!(f, "{count} shared borrow{}", plural(count))?;
+
+            if status.mut_count > 0 {
+                write!(f, " and ")?;
+            }
+        }
+
+        if status.mut_count > 0 {
+            let count = status.mut_count;
+            write!(f, "{count} mutable borrow{}", plural(count))?;
+
+            if status.non_aliasing_count > 0 {
+                write!(f, " ({} non-aliasing)", status.non_aliasing_count)?;
+            }
+        }
+
+        write!(f, " live")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::ToString;
+    use core::pin::pin;
+
+    use super::*;
+    use crate::GdCell;
+
+    #[test]
+    fn short_type_name_strips_paths() {
+        let short = |name| ShortTypeName(name).to_string();
+
+        assert_eq!(short("i32"), "i32");
+        assert_eq!(short("game::Player"), "Player");
+        assert_eq!(short("alloc::vec::Vec<game::Player>"), "Vec<Player>");
+        assert_eq!(
+            short("(core::option::Option<&str>, [u8; 4])"),
+            "(Option<&str>, [u8; 4])"
+        );
+    }
+
+    #[test]
+    fn error_names_type_and_live_borrows() {
+        #[derive(Debug)]
+        struct Player;
+
+        let cell = pin!(GdCell::new(Player));
+        let cell = cell.into_ref();
+
+        let shared1 = cell.gd_ref().unwrap();
+        let shared2 = cell.gd_ref().unwrap();
+        let err = cell.gd_mut().expect_err("shared borrows are live");
+        assert_eq!(
+            err.to_string(),
+            "cannot bind `Player` mutably: 2 shared borrows live"
+        );
+
+        let err = err.downcast_ref::<BorrowErr>().unwrap();
+        assert_eq!(err.error, BorrowStateErr::HasSharedRef);
+        assert_eq!(err.status.shared_count, 2);
+        drop(shared1);
+        drop(shared2);
+
+        let mut guard = cell.gd_mut().unwrap();
+        let err = cell.gd_ref().expect_err("a mutable borrow is live");
+        assert_eq!(
+            err.to_string(),
+            "cannot bind `Player`: 1 mutable borrow live"
+        );
+
+        cell.set_policy(crate::ReentrancyPolicy::Forbid);
+        let err = cell
+            .set_non_aliasing(&mut *guard)
+            .expect_err("reentrancy is forbidden");
+        assert_eq!(
+            err.to_string(),
+            "cannot mark the mutable binding of `Player` as non-aliasing: borrow rejected by reentrancy \
+             policy: `Forbid` does not allow reentrant borrows (1 mutable borrow live)"
+        );
+    }
+}
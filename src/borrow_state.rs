@@ -1,14 +1,29 @@
+use alloc::string::String;
+#[cfg(feature = "deadlock-detection")]
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "deadlock-detection")]
+use std::thread::ThreadId;
+
 use thiserror::Error;
 
-/// A type that tracks the state of borrows for a [`GdCell`].
+use crate::policy::ReentrancyPolicy;
+
+/// A type that tracks the state of borrows for a [`GdCell`](crate::GdCell).
 ///
 /// This state upholds these invariants:
 /// - You can only take a shared borrow when there is no aliasing mutable borrow.
 /// - You can only take a mutable borrow when there is neither an aliasing mutable borrow, nor a shared
-/// borrow.
+///   borrow.
 /// - You can only set a mutable borrow as non-aliasing when an aliasing mutable borrow exists.
 /// - You can only unset a mutable borrow as non-aliasing when there is no aliasing mutable borrow and no
-/// shared borrows.  
+///   shared borrows.
+/// - You can only take an upgradable borrow when you could take a shared borrow, and there is no other
+///   upgradable borrow. An upgradable borrow counts as a shared borrow.
+/// - You can only upgrade an upgradable borrow when it is the only shared borrow.
+/// - You can only take or upgrade into a mutable borrow when the innermost non-aliasing borrow is not
+///   read-only.
+/// - You can only set a mutable borrow as non-aliasing when the [`ReentrancyPolicy`] allows it.
 #[derive(Debug, Clone, PartialEq)]
 pub struct BorrowState {
     /// The number of `&T` references that are tracked.
@@ -17,18 +32,33 @@ pub struct BorrowState {
     mut_count: usize,
     /// The number of `&mut T` references that cannot be aliased.
     non_aliasing_count: usize,
+    /// `true` if the innermost non-aliasing `&mut T` reference only allows new `&T` references.
+    readonly: bool,
+    /// `true` if one of the tracked `&T` references is upgradable.
+    upgradable: bool,
     /// `true` if the borrow state has reached an erroneous or unreliable state.
     poisoned: bool,
+    /// Which kinds of reentrant references are allowed.
+    policy: ReentrancyPolicy,
 }
 
 impl BorrowState {
     /// Create a new borrow state representing no borrows.
     pub fn new() -> Self {
+        Self::with_policy(ReentrancyPolicy::default())
+    }
+
+    /// Create a new borrow state representing no borrows, which only allows reentrant references permitted
+    /// by `policy`.
+    pub fn with_policy(policy: ReentrancyPolicy) -> Self {
         Self {
             shared_count: 0,
             mut_count: 0,
             non_aliasing_count: 0,
+            readonly: false,
+            upgradable: false,
             poisoned: false,
+            policy,
         }
     }
 
@@ -67,6 +97,32 @@ impl BorrowState {
         self.non_aliasing_count
     }
 
+    /// Returns `true` if the innermost non-aliasing mutable reference was set with
+    /// [`Self::set_non_aliasing_readonly`].
+    ///
+    /// While this is the case, [`Self::increment_mut`] and [`Self::upgrade`] cannot succeed.
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
+    /// Returns `true` if one of the tracked shared references is upgradable.
+    pub fn has_upgradable(&self) -> bool {
+        self.upgradable
+    }
+
+    /// Returns the policy deciding which kinds of reentrant references are allowed.
+    pub fn policy(&self) -> ReentrancyPolicy {
+        self.policy
+    }
+
+    /// Change the policy deciding which kinds of reentrant references are allowed.
+    ///
+    /// Existing references are unaffected, but lowering the maximum depth below the current number of
+    /// non-aliasing references prevents new mutable references until enough of them are unset.
+    pub fn set_policy(&mut self, policy: ReentrancyPolicy) {
+        self.policy = policy;
+    }
+
     /// Returns `true` if the state has reached an erroneous or unreliable state.
     pub fn is_poisoned(&self) -> bool {
         self.poisoned
@@ -99,9 +155,41 @@ impl BorrowState {
         Ok(())
     }
 
+    fn ensure_not_readonly(&self) -> Result<(), BorrowStateErr> {
+        if !self.readonly {
+            return Ok(());
+        }
+
+        match self.policy {
+            ReentrancyPolicy::SharedOnly => Err(BorrowStateErr::PolicyViolation(self.policy)),
+            _ => Err(BorrowStateErr::IsReadOnly),
+        }
+    }
+
+    fn ensure_policy_allows_non_aliasing(&self) -> Result<(), BorrowStateErr> {
+        match self.policy {
+            ReentrancyPolicy::Forbid => Err(BorrowStateErr::PolicyViolation(self.policy)),
+            ReentrancyPolicy::Mutable { max_depth } if self.non_aliasing_count >= max_depth => {
+                Err(BorrowStateErr::reentrancy_limit(max_depth))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn ensure_within_max_depth(&self) -> Result<(), BorrowStateErr> {
+        match self.policy {
+            ReentrancyPolicy::Mutable { max_depth } if self.non_aliasing_count > max_depth => {
+                Err(BorrowStateErr::reentrancy_limit(max_depth))
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn ensure_can_mut_ref(&self) -> Result<(), BorrowStateErr> {
         self.ensure_not_poisoned()?;
 
+        self.ensure_not_readonly()?;
+
         if self.has_possibly_aliasing() {
             return Err(BorrowStateErr::HasAliasingRef);
         }
@@ -110,6 +198,8 @@ impl BorrowState {
             return Err(BorrowStateErr::HasSharedRef);
         }
 
+        self.ensure_within_max_depth()?;
+
         Ok(())
     }
 
@@ -138,11 +228,11 @@ impl BorrowState {
     /// Returns the new total number of shared references.
     ///
     /// This fails when:
-    /// - There are currently no tracked shared references.
+    /// - There are currently no tracked shared references, other than the upgradable one.
     pub fn decrement_shared(&mut self) -> Result<usize, BorrowStateErr> {
         self.ensure_not_poisoned()?;
 
-        if self.shared_count == 0 {
+        if self.shared_count == usize::from(self.upgradable) {
             return Err(BorrowStateErr::NoSharedRef);
         }
 
@@ -156,6 +246,95 @@ impl BorrowState {
         Ok(self.shared_count)
     }
 
+    /// Track a new upgradable shared reference.
+    ///
+    /// Returns the new total number of shared references.
+    ///
+    /// This fails when:
+    /// - There exists a possibly aliasing mutable reference.
+    /// - There exists an upgradable reference.
+    /// - There exist `usize::MAX` shared references.
+    pub fn increment_upgradable(&mut self) -> Result<usize, BorrowStateErr> {
+        self.ensure_can_ref()?;
+
+        if self.upgradable {
+            return Err(BorrowStateErr::HasUpgradableRef);
+        }
+
+        self.shared_count = self
+            .shared_count
+            .checked_add(1)
+            .ok_or("could not increment shared count")?;
+        self.upgradable = true;
+
+        Ok(self.shared_count)
+    }
+
+    /// Untrack the existing upgradable shared reference.
+    ///
+    /// Returns the new total number of shared references.
+    ///
+    /// This fails when:
+    /// - There is currently no upgradable reference.
+    pub fn decrement_upgradable(&mut self) -> Result<usize, BorrowStateErr> {
+        self.ensure_not_poisoned()?;
+
+        if !self.upgradable {
+            return Err(BorrowStateErr::NoUpgradableRef);
+        }
+
+        if self.shared_count == 0 {
+            self.poison("upgradable reference tracked without a shared reference")?;
+        }
+
+        if self.has_possibly_aliasing() {
+            self.poison("upgradable reference tracked while aliasing mutable reference exists")?;
+        }
+
+        self.shared_count -= 1;
+        self.upgradable = false;
+
+        Ok(self.shared_count)
+    }
+
+    /// Turn the existing upgradable shared reference into a mutable reference.
+    ///
+    /// Returns the new total number of mutable references.
+    ///
+    /// This fails when:
+    /// - There is currently no upgradable reference.
+    /// - There exists a shared reference other than the upgradable one.
+    /// - The innermost non-aliasing mutable reference is read-only.
+    /// - There are `usize::MAX` tracked mutable references.
+    pub fn upgrade(&mut self) -> Result<usize, BorrowStateErr> {
+        self.ensure_not_poisoned()?;
+
+        self.ensure_not_readonly()?;
+
+        self.ensure_within_max_depth()?;
+
+        if !self.upgradable {
+            return Err(BorrowStateErr::NoUpgradableRef);
+        }
+
+        if self.shared_count != 1 {
+            return Err(BorrowStateErr::HasSharedRef);
+        }
+
+        if self.has_possibly_aliasing() {
+            self.poison("upgradable reference tracked while aliasing mutable reference exists")?;
+        }
+
+        self.mut_count = self
+            .mut_count
+            .checked_add(1)
+            .ok_or("could not increment mut count")?;
+        self.shared_count = 0;
+        self.upgradable = false;
+
+        Ok(self.mut_count)
+    }
+
     /// Track a new mutable reference.
     ///
     /// Returns the new total number of mutable references.
@@ -163,9 +342,11 @@ impl BorrowState {
     /// This fails when:
     /// - There exists a possibly aliasing mutable reference.
     /// - There exists a shared reference.
+    /// - The innermost non-aliasing mutable reference is read-only.
+    /// - There are more non-aliasing references than the [`ReentrancyPolicy`] allows.
     /// - There are `usize::MAX` tracked mutable references.
     ///
-    /// Any amount of shared references will prevent [`Self::increment_non_aliasing`] from succeeding.
+    /// Any amount of shared references will prevent [`Self::set_non_aliasing`] from succeeding.
     pub fn increment_mut(&mut self) -> Result<usize, BorrowStateErr> {
         self.ensure_not_poisoned()?;
 
@@ -213,19 +394,42 @@ impl BorrowState {
     ///
     /// Fails when:
     /// - There is no current
+    /// - The [`ReentrancyPolicy`] is [`Forbid`](ReentrancyPolicy::Forbid), or there already are `max_depth`
+    ///   non-aliasing references under [`Mutable`](ReentrancyPolicy::Mutable).
+    ///
+    /// Under [`SharedOnly`](ReentrancyPolicy::SharedOnly), the reference is set as read-only.
     pub fn set_non_aliasing(&mut self) -> Result<usize, BorrowStateErr> {
         if !self.has_possibly_aliasing() {
             return Err(BorrowStateErr::NoAliasingRef);
         }
 
+        self.ensure_policy_allows_non_aliasing()?;
+
         self.non_aliasing_count = self
             .non_aliasing_count
             .checked_add(1)
             .ok_or("could not increment non-aliasing count")?;
 
+        if self.policy == ReentrancyPolicy::SharedOnly {
+            self.readonly = true;
+        }
+
         Ok(self.non_aliasing_count)
     }
 
+    /// Set the current mutable reference as non-aliasing, only allowing new shared references until it is
+    /// unset again.
+    ///
+    /// Returns the new total of non-aliasing mutable references.
+    ///
+    /// Fails in the same cases as [`Self::set_non_aliasing`].
+    pub fn set_non_aliasing_readonly(&mut self) -> Result<usize, BorrowStateErr> {
+        let count = self.set_non_aliasing()?;
+        self.readonly = true;
+
+        Ok(count)
+    }
+
     pub fn unset_non_aliasing(&mut self) -> Result<usize, BorrowStateErr> {
         if self.has_possibly_aliasing() {
             return Err(BorrowStateErr::HasAliasingRef);
@@ -246,17 +450,31 @@ impl BorrowState {
             .non_aliasing_count
             .checked_sub(1)
             .ok_or("could not decrement non-aliasing count")?;
+        // No new mutable references can be made while read-only, so a read-only borrow is always the innermost
+        // one.
+        self.readonly = false;
 
         Ok(self.non_aliasing_count)
     }
 }
 
+impl Default for BorrowState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum BorrowStateErr {
     #[error("expected a tracked shared reference")]
     NoSharedRef,
     #[error("expected no tracked shared references")]
     HasSharedRef,
+    #[error("expected a tracked upgradable reference")]
+    NoUpgradableRef,
+    #[error("expected no tracked upgradable references")]
+    HasUpgradableRef,
     #[error("expected a tracked mutable reference")]
     NoMutRef,
     #[error("expected no tracked mutable references")]
@@ -267,6 +485,29 @@ pub enum BorrowStateErr {
     NoAliasingRef,
     #[error("expected no tracked non-aliasing mutable references")]
     HasAliasingRef,
+    #[error("expected the current non-aliasing reference to allow mutable reentrancy, but it is read-only")]
+    IsReadOnly,
+    #[error("borrow rejected by reentrancy policy: {0}")]
+    PolicyViolation(ReentrancyPolicy),
+    #[error(fmt = fmt_reentrancy_limit)]
+    ReentrancyLimit {
+        /// The maximum depth that was exceeded.
+        depth: usize,
+        /// The address of the [`GdCell`](crate::GdCell) that reached the limit, if known.
+        cell: Option<usize>,
+    },
+    #[error("the same cell was passed more than once")]
+    DuplicateCell,
+    #[error("expected no reentrant borrows on another thread")]
+    HeldByOtherThread,
+    #[cfg(feature = "deadlock-detection")]
+    #[error(fmt = fmt_deadlock)]
+    Deadlock {
+        /// The threads waiting for each other.
+        threads: Vec<ThreadId>,
+        /// The addresses of the cells each of `threads` waits for. Each cell is held by the next thread.
+        cells: Vec<usize>,
+    },
     #[error("borrow state is poisoned and cannot continue")]
     IsPoisoned,
     #[error("borrow state encountered an unexpected state and was poisoned: {0}")]
@@ -275,6 +516,90 @@ pub enum BorrowStateErr {
     Custom(String),
 }
 
+impl BorrowStateErr {
+    fn reentrancy_limit(depth: usize) -> Self {
+        Self::ReentrancyLimit { depth, cell: None }
+    }
+
+    /// Returns the name of this error's variant, such as `"HasSharedRef"`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NoSharedRef => "NoSharedRef",
+            Self::HasSharedRef => "HasSharedRef",
+            Self::NoUpgradableRef => "NoUpgradableRef",
+            Self::HasUpgradableRef => "HasUpgradableRef",
+            Self::NoMutRef => "NoMutRef",
+            Self::HasMutRef => "HasMutRef",
+            Self::IsNonAliasing => "IsNonAliasing",
+            Self::NoAliasingRef => "NoAliasingRef",
+            Self::HasAliasingRef => "HasAliasingRef",
+            Self::IsReadOnly => "IsReadOnly",
+            Self::PolicyViolation(_) => "PolicyViolation",
+            Self::ReentrancyLimit { .. } => "ReentrancyLimit",
+            Self::DuplicateCell => "DuplicateCell",
+            Self::HeldByOtherThread => "HeldByOtherThread",
+            #[cfg(feature = "deadlock-detection")]
+            Self::Deadlock { .. } => "Deadlock",
+            Self::IsPoisoned => "IsPoisoned",
+            Self::Poisoned(_) => "Poisoned",
+            Self::Custom(_) => "Custom",
+        }
+    }
+
+    /// Returns `true` if this error may be resolved by another thread releasing its borrows.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn would_block(&self) -> bool {
+        matches!(
+            self,
+            Self::HasSharedRef
+                | Self::HasUpgradableRef
+                | Self::HasMutRef
+                | Self::HasAliasingRef
+                | Self::HeldByOtherThread
+        )
+    }
+
+    /// Record the address of the cell this error occurred in, if the error refers to a specific cell.
+    pub(crate) fn in_cell(self, address: usize) -> Self {
+        match self {
+            Self::ReentrancyLimit { depth, .. } => Self::ReentrancyLimit {
+                depth,
+                cell: Some(address),
+            },
+            err => err,
+        }
+    }
+}
+
+fn fmt_reentrancy_limit(
+    depth: &usize,
+    cell: &Option<usize>,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    write!(f, "reached the maximum reentrancy depth of {depth}")?;
+
+    match cell {
+        Some(address) => write!(f, " in cell at {address:#x}"),
+        None => Ok(()),
+    }
+}
+
+#[cfg(feature = "deadlock-detection")]
+fn fmt_deadlock(threads: &[ThreadId], cells: &[usize], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "deadlock detected:")?;
+
+    for (i, (thread, cell)) in threads.iter().zip(cells).enumerate() {
+        let holder = threads[(i + 1) % threads.len()];
+        let separator = if i == 0 { "" } else { "," };
+        write!(
+            f,
+            "{separator} {thread:?} waits for cell at {cell:#x} held by {holder:?}"
+        )?;
+    }
+
+    Ok(())
+}
+
 impl<'a> From<&'a str> for BorrowStateErr {
     fn from(value: &'a str) -> Self {
         Self::Custom(value.into())
@@ -289,36 +614,14 @@ impl From<String> for BorrowStateErr {
 
 #[cfg(all(test, not(miri)))]
 mod test {
+    use alloc::vec::Vec;
+
     use super::*;
     use proptest::{collection::vec, prelude::*};
-    use proptest_derive::Arbitrary;
 
-    #[derive(Arbitrary, Debug, Clone, Copy, PartialEq, Eq)]
-    enum Operation {
-        IncShared,
-        DecShared,
-        IncMut,
-        DecMut,
-        SetNoAlias,
-        UnsetNoAlias,
-    }
-
-    impl Operation {
-        fn execute(&self, state: &mut BorrowState) -> Result<(), BorrowStateErr> {
-            use Operation as Op;
+    use crate::trace::BorrowOp;
 
-            let result = match self {
-                Op::IncShared => state.increment_shared(),
-                Op::DecShared => state.decrement_shared(),
-                Op::IncMut => state.increment_mut(),
-                Op::DecMut => state.decrement_mut(),
-                Op::SetNoAlias => state.set_non_aliasing(),
-                Op::UnsetNoAlias => state.unset_non_aliasing(),
-            };
-
-            result.map(|_| ())
-        }
-    }
+    type Operation = BorrowOp;
 
     #[derive(Debug, Clone, PartialEq, Eq)]
     struct OperationExecutor {
@@ -328,7 +631,7 @@ mod test {
     impl OperationExecutor {
         fn execute_all(&self, state: &mut BorrowState) {
             for op in self.vec.iter() {
-                _ = op.execute(state);
+                _ = op.apply(state);
             }
         }
 
@@ -367,6 +670,14 @@ mod test {
         }
     }
 
+    fn arbitrary_policy() -> impl Strategy<Value = ReentrancyPolicy> {
+        prop_oneof![
+            Just(ReentrancyPolicy::Forbid),
+            Just(ReentrancyPolicy::SharedOnly),
+            (0..4usize).prop_map(|max_depth| ReentrancyPolicy::Mutable { max_depth }),
+        ]
+    }
+
     prop_compose! {
         fn arbitrary_ops(max_len: usize)(len in 0..max_len)(operations in vec(any::<Operation>(), len)) -> Vec<Operation> {
             operations
@@ -400,14 +711,36 @@ mod test {
                         original.non_aliasing_count += 1;
                         original
                     },
+                    Op::SetNoAliasReadOnly => |mut original: BorrowState| {
+                        original.non_aliasing_count += 1;
+                        original.readonly = true;
+                        original
+                    },
                     Op::UnsetNoAlias => |mut original: BorrowState| {
                         original.non_aliasing_count -= 1;
+                        original.readonly = false;
+                        original
+                    },
+                    Op::IncUpgradable => |mut original: BorrowState| {
+                        original.shared_count += 1;
+                        original.upgradable = true;
+                        original
+                    },
+                    Op::DecUpgradable => |mut original: BorrowState| {
+                        original.shared_count -= 1;
+                        original.upgradable = false;
+                        original
+                    },
+                    Op::Upgrade => |mut original: BorrowState| {
+                        original.shared_count -= 1;
+                        original.upgradable = false;
+                        original.mut_count += 1;
                         original
                     },
                 };
 
                 let original = state.clone();
-                if op.execute(&mut state).is_ok() {
+                if op.apply(&mut state).is_ok() {
                     assert_eq!(state, expected_on_success(original));
                 } else {
                     assert_eq!(state, original);
@@ -421,7 +754,7 @@ mod test {
         fn no_poison(operations in arbitrary_ops(50)) {
             let mut state = BorrowState::new();
             for op in operations {
-                if let Err(err) = op.execute(&mut state) {
+                if let Err(err) = op.apply(&mut state) {
                     assert_ne!(err, BorrowStateErr::IsPoisoned);
                     assert!(!matches!(err, BorrowStateErr::Poisoned(_)));
                 }
@@ -436,7 +769,7 @@ mod test {
         fn no_shared_and_mut(operations in arbitrary_ops(50)) {
             let mut state = BorrowState::new();
             for op in operations {
-                _ = op.execute(&mut state);
+                _ = op.apply(&mut state);
                 if state.has_shared_reference() {
                     assert!(!state.has_possibly_aliasing())
                 }
@@ -450,7 +783,7 @@ mod test {
             let mut state = BorrowState::new();
 
             for op in operations {
-                _ = op.execute(&mut state);
+                _ = op.apply(&mut state);
                 if state.has_shared_reference() {
                     assert!(state.increment_shared().is_ok());
                     assert!(state.decrement_shared().is_ok());
@@ -465,7 +798,7 @@ mod test {
             let mut state = BorrowState::new();
 
             for op in operations {
-                _ = op.execute(&mut state);
+                _ = op.apply(&mut state);
                 if state.has_possibly_aliasing() {
                     assert!(state.increment_shared().is_err());
                 }
@@ -479,7 +812,7 @@ mod test {
             let mut state = BorrowState::new();
 
             for op in operations {
-                _ = op.execute(&mut state);
+                _ = op.apply(&mut state);
                 if !state.has_possibly_aliasing() {
                     assert!(state.increment_shared().is_ok());
                     assert!(state.decrement_shared().is_ok());
@@ -494,8 +827,8 @@ mod test {
             let mut state = BorrowState::new();
 
             for op in operations {
-                _ = op.execute(&mut state);
-                if !state.has_possibly_aliasing() && !state.has_shared_reference() {
+                _ = op.apply(&mut state);
+                if !state.has_possibly_aliasing() && !state.has_shared_reference() && !state.is_readonly() {
                     assert!(state.increment_mut().is_ok());
                     assert!(state.decrement_mut().is_ok());
                 }
@@ -509,7 +842,7 @@ mod test {
             let mut state = BorrowState::new();
 
             for op in operations {
-                _ = op.execute(&mut state);
+                _ = op.apply(&mut state);
                 if state.has_shared_reference() {
                     assert!(state.increment_mut().is_err());
                 }
@@ -523,7 +856,7 @@ mod test {
             let mut state = BorrowState::new();
 
             for op in operations {
-                _ = op.execute(&mut state);
+                _ = op.apply(&mut state);
                 if state.has_possibly_aliasing() {
                     assert!(state.increment_mut().is_err());
                 }
@@ -531,13 +864,28 @@ mod test {
         }
     }
 
+    proptest! {
+        #[test]
+        fn cannot_borrow_mut_when_readonly(operations in arbitrary_ops(50)) {
+            let mut state = BorrowState::new();
+
+            for op in operations {
+                _ = op.apply(&mut state);
+                if state.is_readonly() {
+                    assert_eq!(state.increment_mut(), Err(BorrowStateErr::IsReadOnly));
+                    assert!(state.upgrade().is_err());
+                }
+            }
+        }
+    }
+
     proptest! {
         #[test]
         fn can_set_nonaliasing_when_aliasing(operations in arbitrary_ops(50)) {
             let mut state = BorrowState::new();
 
             for op in operations {
-                _ = op.execute(&mut state);
+                _ = op.apply(&mut state);
                 if state.has_possibly_aliasing() {
                     assert!(state.set_non_aliasing().is_ok());
                     assert!(state.unset_non_aliasing().is_ok());
@@ -552,7 +900,7 @@ mod test {
             let mut state = BorrowState::new();
 
             for op in operations {
-                _ = op.execute(&mut state);
+                _ = op.apply(&mut state);
                 if state.has_shared_reference() {
                     assert!(state.set_non_aliasing().is_err());
                 }
@@ -566,7 +914,7 @@ mod test {
             let mut state = BorrowState::new();
 
             for op in operations {
-                _ = op.execute(&mut state);
+                _ = op.apply(&mut state);
                 if !state.has_possibly_aliasing() {
                     assert!(state.set_non_aliasing().is_err());
                 }
@@ -574,6 +922,123 @@ mod test {
         }
     }
 
+    proptest! {
+        #[test]
+        fn cannot_borrow_upgradable_when_upgradable(operations in arbitrary_ops(50)) {
+            let mut state = BorrowState::new();
+
+            for op in operations {
+                _ = op.apply(&mut state);
+                if state.has_upgradable() {
+                    assert!(state.increment_upgradable().is_err());
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn cannot_borrow_mut_when_upgradable(operations in arbitrary_ops(50)) {
+            let mut state = BorrowState::new();
+
+            for op in operations {
+                _ = op.apply(&mut state);
+                if state.has_upgradable() {
+                    assert!(state.increment_mut().is_err());
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn can_borrow_shared_when_upgradable(operations in arbitrary_ops(50)) {
+            let mut state = BorrowState::new();
+
+            for op in operations {
+                _ = op.apply(&mut state);
+                if state.has_upgradable() {
+                    assert!(state.increment_shared().is_ok());
+                    assert!(state.decrement_shared().is_ok());
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn can_upgrade_when_only_upgradable(operations in arbitrary_ops(50)) {
+            let mut state = BorrowState::new();
+
+            for op in operations {
+                _ = op.apply(&mut state);
+                if state.has_upgradable() && state.shared_count() == 1 && !state.is_readonly() {
+                    assert!(state.upgrade().is_ok());
+                    assert!(!state.has_shared_reference());
+                    assert!(state.has_possibly_aliasing());
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn cannot_upgrade_when_shared(operations in arbitrary_ops(50)) {
+            let mut state = BorrowState::new();
+
+            for op in operations {
+                _ = op.apply(&mut state);
+                if state.shared_count() > usize::from(state.has_upgradable()) {
+                    assert!(state.upgrade().is_err());
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn policy_is_upheld(policy in arbitrary_policy(), operations in arbitrary_ops(50)) {
+            let mut state = BorrowState::with_policy(policy);
+
+            for op in operations {
+                _ = op.apply(&mut state);
+                match policy {
+                    ReentrancyPolicy::Forbid => assert_eq!(state.non_aliasing_count(), 0),
+                    ReentrancyPolicy::SharedOnly => assert!(state.mut_count() <= 1),
+                    ReentrancyPolicy::Mutable { max_depth } => {
+                        assert!(state.non_aliasing_count() <= max_depth)
+                    }
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn policy_violations_are_named(policy in arbitrary_policy(), operations in arbitrary_ops(50)) {
+            let mut state = BorrowState::with_policy(policy);
+
+            for op in operations {
+                let err = op.apply(&mut state).err();
+
+                if let Some(BorrowStateErr::ReentrancyLimit { depth, .. }) = err {
+                    assert_eq!(ReentrancyPolicy::Mutable { max_depth: depth }, policy);
+                }
+
+                if let Some(BorrowStateErr::PolicyViolation(violated)) = err {
+                    assert_eq!(violated, policy);
+                    assert!(matches!(
+                        op,
+                        Operation::IncMut
+                            | Operation::Upgrade
+                            | Operation::SetNoAlias
+                            | Operation::SetNoAliasReadOnly
+                    ));
+                }
+            }
+        }
+    }
+
     proptest! {
         #[test]
         fn remove_shared_inc_dec_pairs_is_noop(operations in arbitrary_ops(50)) {
@@ -589,6 +1054,38 @@ mod test {
         }
     }
 
+    #[test]
+    fn lowered_max_depth_prevents_mut() {
+        let mut state = BorrowState::new();
+
+        state.increment_mut().unwrap();
+        state.set_non_aliasing().unwrap();
+        state.increment_mut().unwrap();
+        state.set_non_aliasing().unwrap();
+
+        state.set_policy(ReentrancyPolicy::Mutable { max_depth: 1 });
+        assert_eq!(
+            state.increment_mut(),
+            Err(BorrowStateErr::ReentrancyLimit {
+                depth: 1,
+                cell: None
+            })
+        );
+
+        state.unset_non_aliasing().unwrap();
+        assert_eq!(
+            state.set_non_aliasing(),
+            Err(BorrowStateErr::ReentrancyLimit {
+                depth: 1,
+                cell: None
+            })
+        );
+
+        state.decrement_mut().unwrap();
+        assert!(state.increment_mut().is_ok());
+        assert!(!state.is_poisoned());
+    }
+
     #[test]
     fn poisoned_unset_shared_ref() {
         let mut state = BorrowState::new();
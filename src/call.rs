@@ -0,0 +1,202 @@
+//! Calling methods of instances in the registry by their id.
+
+use alloc::{boxed::Box, string::String};
+use core::{error::Error as StdError, panic::Location};
+
+use thiserror::Error;
+
+use crate::borrow_err::BorrowErr;
+use crate::hooks::BorrowEventKind;
+use crate::registry::{try_instance, InstanceErr, InstanceId};
+
+/// Error returned when calling a method of an instance in the registry fails.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum CallErr {
+    /// The instance could not be found.
+    #[error(transparent)]
+    Instance(#[from] InstanceErr),
+    /// The instance could not be borrowed.
+    #[error(transparent)]
+    Borrow(#[from] BorrowErr),
+    /// The method itself failed, see [`try_call_ref`] and [`try_call_mut`].
+    #[error(transparent)]
+    Method(Box<dyn StdError + Send + Sync>),
+    /// The type of the instance has no method of this name, see [`call_by_name`](crate::call_by_name).
+    #[error("instance {id} has no method `{method}`")]
+    UnknownMethod { id: InstanceId, method: String },
+    /// The arguments passed to the method are not of the type it takes.
+    #[error("method `{method}` expects arguments of type `{expected}`")]
+    WrongType {
+        method: String,
+        expected: &'static str,
+    },
+}
+
+/// Call `f` with a shared borrow of the instance with id `id` and `args`, returning what `f` returns.
+///
+/// `f` can be a method taking `&T` and one argument, or a closure. Several arguments can be passed as a
+/// tuple.
+#[track_caller]
+pub fn call_ref<T, A, R>(id: InstanceId, f: impl FnOnce(&T, A) -> R, args: A) -> Result<R, CallErr>
+where
+    T: Send + Sync + 'static,
+{
+    call_ref_at(id, f, args, Location::caller())
+}
+
+/// Call `f` like [`call_ref`], but attribute the borrow to `location` instead of the caller.
+pub(crate) fn call_ref_at<T, A, R>(
+    id: InstanceId,
+    f: impl FnOnce(&T, A) -> R,
+    args: A,
+    location: &'static Location<'static>,
+) -> Result<R, CallErr>
+where
+    T: Send + Sync + 'static,
+{
+    let instance = try_instance::<T>(id)?;
+    let cell = instance.cell();
+    let guard = cell.check_at(
+        BorrowEventKind::Shared,
+        cell.get_ref().try_ref(cell.event_source().at(location)),
+        location,
+    )?;
+
+    Ok(f(&guard, args))
+}
+
+/// Call `f` with a mutable borrow of the instance with id `id` and `args`, like [`call_ref`].
+#[track_caller]
+pub fn call_mut<T, A, R>(
+    id: InstanceId,
+    f: impl FnOnce(&mut T, A) -> R,
+    args: A,
+) -> Result<R, CallErr>
+where
+    T: Send + Sync + 'static,
+{
+    call_mut_at(id, f, args, Location::caller())
+}
+
+/// Call `f` like [`call_mut`], but attribute the borrow to `location` instead of the caller.
+pub(crate) fn call_mut_at<T, A, R>(
+    id: InstanceId,
+    f: impl FnOnce(&mut T, A) -> R,
+    args: A,
+    location: &'static Location<'static>,
+) -> Result<R, CallErr>
+where
+    T: Send + Sync + 'static,
+{
+    let instance = try_instance::<T>(id)?;
+    let cell = instance.cell();
+    let mut guard = cell.check_at(
+        BorrowEventKind::Mut,
+        cell.try_mut(cell.event_source().at(location)),
+        location,
+    )?;
+
+    Ok(f(&mut guard, args))
+}
+
+/// Call a fallible `f` like [`call_ref`], returning its error as [`CallErr::Method`].
+#[track_caller]
+pub fn try_call_ref<T, A, R, E>(
+    id: InstanceId,
+    f: impl FnOnce(&T, A) -> Result<R, E>,
+    args: A,
+) -> Result<R, CallErr>
+where
+    T: Send + Sync + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    call_ref(id, f, args)?.map_err(|err| CallErr::Method(err.into()))
+}
+
+/// Call a fallible `f` like [`call_mut`], returning its error as [`CallErr::Method`].
+#[track_caller]
+pub fn try_call_mut<T, A, R, E>(
+    id: InstanceId,
+    f: impl FnOnce(&mut T, A) -> Result<R, E>,
+    args: A,
+) -> Result<R, CallErr>
+where
+    T: Send + Sync + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    call_mut(id, f, args)?.map_err(|err| CallErr::Method(err.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::string::ToString;
+
+    use super::*;
+    use crate::{register_instance, BorrowStateErr};
+
+    #[test]
+    fn calls_report_typed_errors() {
+        let id = register_instance(1);
+
+        assert_eq!(
+            call_mut(id, |value: &mut i32, n| *value += n, 1).ok(),
+            Some(())
+        );
+        assert_eq!(call_ref(id, |value: &i32, ()| *value, ()).ok(), Some(2));
+
+        let err = call_mut(
+            id,
+            |_: &mut i32, ()| call_ref(id, |value: &i32, ()| *value, ()),
+            (),
+        );
+        let err = err.unwrap();
+        let Err(CallErr::Borrow(err)) = err else {
+            panic!("expected a borrow error, got {err:?}");
+        };
+        assert_eq!(err.error, BorrowStateErr::HasAliasingRef);
+
+        assert!(matches!(
+            call_ref(id, |_: &u8, ()| (), ()),
+            Err(CallErr::Instance(InstanceErr::WrongType { .. }))
+        ));
+    }
+
+    struct Account {
+        balance: u32,
+    }
+
+    impl Account {
+        fn withdraw(&mut self, amount: u32) -> Result<u32, &'static str> {
+            self.balance = self
+                .balance
+                .checked_sub(amount)
+                .ok_or("insufficient funds")?;
+            Ok(self.balance)
+        }
+    }
+
+    #[test]
+    fn fallible_calls_combine_errors() {
+        let id = register_instance(Account { balance: 10 });
+
+        assert_eq!(try_call_mut(id, Account::withdraw, 4).ok(), Some(6));
+
+        let err = try_call_mut(id, Account::withdraw, 7).unwrap_err();
+        assert!(matches!(err, CallErr::Method(_)));
+        assert_eq!(err.to_string(), "insufficient funds");
+
+        let err = call_mut(
+            id,
+            |_: &mut Account, ()| {
+                try_call_ref(
+                    id,
+                    |account: &Account, ()| Ok::<_, &str>(account.balance),
+                    (),
+                )
+            },
+            (),
+        );
+        assert!(matches!(err, Ok(Err(CallErr::Borrow(_)))));
+    }
+}
@@ -0,0 +1,68 @@
+#[cfg(feature = "std")]
+use std::thread::{self, ThreadId};
+
+use crate::borrow_state::{BorrowState, BorrowStateErr};
+#[cfg(feature = "history")]
+use crate::history::History;
+use crate::hooks::BorrowEventKind;
+#[cfg(feature = "stats")]
+use crate::stats::BorrowStats;
+
+/// Everything a [`GdCell`](crate::GdCell) protects with its state lock.
+#[derive(Debug)]
+pub struct CellState {
+    /// The borrows of the cell.
+    pub borrow: BorrowState,
+    /// The most recent transitions of `borrow`, if enabled.
+    #[cfg(feature = "history")]
+    pub history: Option<History>,
+    /// The counters of borrows of the cell.
+    #[cfg(feature = "stats")]
+    pub stats: BorrowStats,
+    /// The thread which marked the outermost non-aliasing borrow, if any.
+    #[cfg(feature = "std")]
+    reentrant_thread: Option<ThreadId>,
+}
+
+impl CellState {
+    pub fn new(borrow: BorrowState) -> Self {
+        Self {
+            borrow,
+            #[cfg(feature = "history")]
+            history: None,
+            #[cfg(feature = "stats")]
+            stats: BorrowStats::new(),
+            #[cfg(feature = "std")]
+            reentrant_thread: None,
+        }
+    }
+
+    /// Ensure the current thread may attempt a transition of `kind`.
+    ///
+    /// While a borrow is marked as non-aliasing, new borrows are derived from it. So only the thread which
+    /// marked it may take new borrows, any other thread could otherwise alias the reentrant borrows of that
+    /// thread.
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    pub fn ensure_may_borrow(&self, kind: BorrowEventKind) -> Result<(), BorrowStateErr> {
+        #[cfg(feature = "std")]
+        if kind.is_acquire()
+            && self
+                .reentrant_thread
+                .is_some_and(|owner| owner != thread::current().id())
+        {
+            return Err(BorrowStateErr::HeldByOtherThread);
+        }
+
+        Ok(())
+    }
+
+    /// Update which thread may reenter the cell after a transition of `borrow`.
+    pub fn update_reentrant_thread(&mut self) {
+        #[cfg(feature = "std")]
+        match (self.borrow.non_aliasing_count(), self.reentrant_thread) {
+            (0, _) => self.reentrant_thread = None,
+            (_, None) => self.reentrant_thread = Some(thread::current().id()),
+            (_, Some(_)) => {}
+        }
+    }
+}
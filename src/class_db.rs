@@ -0,0 +1,224 @@
+//! Calling methods of instances in the registry by name, for scripts which only know the name of a method.
+
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
+use core::{
+    any::{type_name, Any, TypeId},
+    panic::Location,
+};
+
+use crate::call::{call_mut_at, call_ref_at, CallErr};
+use crate::lock::{DefaultLock, Lock};
+use crate::registry::{instance_type_id, InstanceId};
+use crate::GdCell;
+
+/// How a method registered in the [`ClassDb`] binds its instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindMode {
+    /// The method takes `&self`, and is called with a shared borrow.
+    Shared,
+    /// The method takes `&mut self`, and is called with a mutable borrow.
+    Mutable,
+}
+
+/// A method taking the id of its instance, its arguments and the location to attribute the borrow to.
+type ErasedMethod = dyn Fn(InstanceId, Box<dyn Any>, &'static Location<'static>) -> Result<Box<dyn Any>, CallErr>
+    + Send
+    + Sync;
+
+struct Method {
+    mode: BindMode,
+    // An `Arc`, so it can be called after the class database is unlocked.
+    call: Arc<ErasedMethod>,
+}
+
+/// The methods of every class, by the type id of the cell storing an instance of the class.
+static CLASSES: DefaultLock<BTreeMap<TypeId, BTreeMap<&'static str, Method>>> =
+    DefaultLock::new(BTreeMap::new());
+
+/// The database of named methods of each type stored in the registry, see [`call_by_name`].
+pub struct ClassDb;
+
+impl ClassDb {
+    /// Register `method` as the method `name` of `T`, taking `&T`.
+    ///
+    /// The arguments and the result are passed as [`Any`], several arguments can be passed as a tuple.
+    /// Registering a method with the same name again replaces it.
+    pub fn register_ref<T, A, R>(name: &'static str, method: fn(&T, A) -> R)
+    where
+        T: Send + Sync + 'static,
+        A: 'static,
+        R: 'static,
+    {
+        Self::register::<T>(name, BindMode::Shared, move |id, args, location| {
+            let args = downcast_args::<A>(name, args)?;
+            Ok(Box::new(call_ref_at(id, method, args, location)?))
+        });
+    }
+
+    /// Register `method` as the method `name` of `T`, taking `&mut T`, like [`Self::register_ref`].
+    pub fn register_mut<T, A, R>(name: &'static str, method: fn(&mut T, A) -> R)
+    where
+        T: Send + Sync + 'static,
+        A: 'static,
+        R: 'static,
+    {
+        Self::register::<T>(name, BindMode::Mutable, move |id, args, location| {
+            let args = downcast_args::<A>(name, args)?;
+            Ok(Box::new(call_mut_at(id, method, args, location)?))
+        });
+    }
+
+    fn register<T: 'static>(
+        name: &'static str,
+        mode: BindMode,
+        call: impl Fn(
+                InstanceId,
+                Box<dyn Any>,
+                &'static Location<'static>,
+            ) -> Result<Box<dyn Any>, CallErr>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        let method = Method {
+            mode,
+            call: Arc::new(call),
+        };
+
+        CLASSES
+            .lock()
+            .entry(TypeId::of::<GdCell<T>>())
+            .or_default()
+            .insert(name, method);
+    }
+
+    /// Returns how the method `name` of `T` binds its instance, or `None` if `T` has no such method.
+    pub fn method_mode<T: 'static>(name: &str) -> Option<BindMode> {
+        let classes = CLASSES.lock();
+        let method = classes.get(&TypeId::of::<GdCell<T>>())?.get(name)?;
+
+        Some(method.mode)
+    }
+}
+
+fn downcast_args<A: 'static>(method: &'static str, args: Box<dyn Any>) -> Result<A, CallErr> {
+    args.downcast::<A>()
+        .map(|args| *args)
+        .map_err(|_| CallErr::WrongType {
+            method: method.into(),
+            expected: type_name::<A>(),
+        })
+}
+
+/// Call the method `name` of the instance with id `id` with `args`, and return its result.
+///
+/// The instance is borrowed as the [`BindMode`] of the method requires. Fails with
+/// [`CallErr::UnknownMethod`] if the type of the instance has no method `name` in the [`ClassDb`], with
+/// [`CallErr::WrongType`] if `args` are not of the type the method takes, or if the instance cannot be
+/// borrowed.
+#[track_caller]
+pub fn call_by_name(
+    id: InstanceId,
+    name: &str,
+    args: Box<dyn Any>,
+) -> Result<Box<dyn Any>, CallErr> {
+    let type_id = instance_type_id(id)?;
+    let call = CLASSES
+        .lock()
+        .get(&type_id)
+        .and_then(|methods| methods.get(name))
+        .map(|method| method.call.clone())
+        .ok_or_else(|| CallErr::UnknownMethod {
+            id,
+            method: name.into(),
+        })?;
+
+    call(id, args, Location::caller())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::error_handler::TEST_HANDLER_LOCK;
+    use crate::{
+        register_instance, register_ref_counted, remove_borrow_error_handler,
+        set_borrow_error_handler, BorrowStateErr,
+    };
+
+    struct Door {
+        open: bool,
+    }
+
+    impl Door {
+        fn is_open(&self, (): ()) -> bool {
+            self.open
+        }
+
+        fn set_open(&mut self, open: bool) {
+            self.open = open;
+        }
+    }
+
+    #[test]
+    fn calls_methods_by_name() {
+        ClassDb::register_ref("is_open", Door::is_open);
+        ClassDb::register_mut("set_open", Door::set_open);
+        assert_eq!(
+            ClassDb::method_mode::<Door>("set_open"),
+            Some(BindMode::Mutable)
+        );
+
+        let id = register_instance(Door { open: false });
+        call_by_name(id, "set_open", Box::new(true)).unwrap();
+        let open = call_by_name(id, "is_open", Box::new(())).unwrap();
+        assert_eq!(open.downcast_ref::<bool>(), Some(&true));
+
+        assert!(matches!(
+            call_by_name(id, "close", Box::new(())),
+            Err(CallErr::UnknownMethod { .. })
+        ));
+        assert!(matches!(
+            call_by_name(id, "set_open", Box::new("yes")),
+            Err(CallErr::WrongType { .. })
+        ));
+        assert!(matches!(
+            call_by_name(register_instance(0), "is_open", Box::new(())),
+            Err(CallErr::UnknownMethod { .. })
+        ));
+    }
+
+    /// Only stored in the instance of `failures_are_reported_at_caller`, so its failures can be told apart.
+    struct Latch;
+
+    static FAILURE_LINES: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+    fn record_failure(
+        _: &BorrowStateErr,
+        type_name: &'static str,
+        location: &'static Location<'static>,
+    ) {
+        if type_name.ends_with("Latch") {
+            FAILURE_LINES.lock().unwrap().push(location.line());
+        }
+    }
+
+    #[test]
+    fn failures_are_reported_at_caller() {
+        let _handler = TEST_HANDLER_LOCK.lock().unwrap();
+        set_borrow_error_handler(record_failure);
+
+        ClassDb::register_mut("pull", |_: &mut Latch, ()| ());
+        let instance = register_ref_counted(Latch);
+        let bound = instance.bind().unwrap();
+        let line = line!() + 1;
+        assert!(call_by_name(instance.id(), "pull", Box::new(())).is_err());
+        drop(bound);
+
+        remove_borrow_error_handler();
+        // Not where the method was registered.
+        assert_eq!(*FAILURE_LINES.lock().unwrap(), [line]);
+    }
+}
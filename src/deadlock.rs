@@ -0,0 +1,253 @@
+//! A wait-for graph across all [`GdCell`](crate::GdCell)s, to detect blocking borrows which would wait
+//! forever.
+//!
+//! Only available with the `deadlock-detection` feature. The graph records which threads hold borrows of
+//! which cells, and which cell each blocked thread waits for. A blocking borrow which would complete a cycle
+//! in this graph fails with [`BorrowStateErr::Deadlock`] instead of waiting.
+//!
+//! Borrows of arc guards, like [`GdCell::gd_mut_arc`](crate::GdCell::gd_mut_arc), may be moved to another
+//! thread, so they are held by no thread and never part of a detected deadlock.
+
+use alloc::vec::Vec;
+use std::thread::{self, ThreadId};
+
+use crate::borrow_state::BorrowStateErr;
+use crate::hooks::BorrowEventKind;
+use crate::lock::{DefaultLock, Lock};
+
+struct WaitGraph {
+    /// The cell each blocked thread waits for.
+    waiting: Vec<(ThreadId, usize)>,
+    /// The thread holding each live borrow, by the address of the borrowed cell. Borrows whose guard may be
+    /// moved to another thread are held by no thread.
+    holders: Vec<(usize, Option<ThreadId>)>,
+}
+
+static GRAPH: DefaultLock<WaitGraph> = DefaultLock::new(WaitGraph {
+    waiting: Vec::new(),
+    holders: Vec::new(),
+});
+
+impl WaitGraph {
+    fn waiting_for(&self, thread: ThreadId) -> Option<usize> {
+        self.waiting
+            .iter()
+            .find(|(waiter, _)| *waiter == thread)
+            .map(|(_, cell)| *cell)
+    }
+
+    fn holders_of(&self, cell: usize) -> impl Iterator<Item = ThreadId> + '_ {
+        self.holders
+            .iter()
+            .filter(move |(held, _)| *held == cell)
+            .filter_map(|(_, thread)| *thread)
+    }
+
+    fn stop_waiting(&mut self, thread: ThreadId) {
+        self.waiting.retain(|(waiter, _)| *waiter != thread);
+    }
+
+    /// Returns the threads and the cells they wait for which form a cycle through `start`, if any.
+    fn find_cycle(&self, start: ThreadId) -> Option<Vec<(ThreadId, usize)>> {
+        let mut path = Vec::new();
+        let mut visited = Vec::new();
+
+        self.visit(start, start, &mut path, &mut visited)
+            .then_some(path)
+    }
+
+    fn visit(
+        &self,
+        thread: ThreadId,
+        start: ThreadId,
+        path: &mut Vec<(ThreadId, usize)>,
+        visited: &mut Vec<ThreadId>,
+    ) -> bool {
+        let Some(cell) = self.waiting_for(thread) else {
+            return false;
+        };
+        path.push((thread, cell));
+
+        for holder in self.holders_of(cell) {
+            if holder == start {
+                return true;
+            }
+
+            if !visited.contains(&holder) {
+                visited.push(holder);
+
+                if self.visit(holder, start, path, visited) {
+                    return true;
+                }
+            }
+        }
+
+        path.pop();
+        false
+    }
+}
+
+/// Record that the current thread took or released a borrow of the cell at address `cell`.
+///
+/// Must be called with the state lock of the cell locked, so the graph never lags behind the cell.
+pub fn record_transition(cell: usize, kind: BorrowEventKind) {
+    use BorrowEventKind as Kind;
+
+    match kind {
+        Kind::Shared | Kind::Upgradable | Kind::Mut => {
+            GRAPH
+                .lock()
+                .holders
+                .push((cell, Some(thread::current().id())));
+        }
+        Kind::ReleaseShared | Kind::ReleaseUpgradable | Kind::ReleaseMut => {
+            let thread = thread::current().id();
+            let mut graph = GRAPH.lock();
+
+            // Guards which own their cell may be released on another thread than they were taken on. So if
+            // the current thread holds nothing, attribute the release to a borrow held by no thread, or else
+            // to any holder of the cell.
+            let position = graph
+                .holders
+                .iter()
+                .position(|&held| held == (cell, Some(thread)))
+                .or_else(|| graph.holders.iter().position(|&held| held == (cell, None)))
+                .or_else(|| graph.holders.iter().position(|&(held, _)| held == cell));
+
+            if let Some(i) = position {
+                graph.holders.swap_remove(i);
+            }
+        }
+        Kind::Upgrade
+        | Kind::SetNonAliasing
+        | Kind::SetNonAliasingReadOnly
+        | Kind::UnsetNonAliasing
+        | Kind::Poisoned => {}
+    }
+}
+
+/// Record that a borrow of the cell at address `cell` taken by the current thread is held by no thread, since
+/// its guard may be moved to another thread.
+///
+/// Threads waiting for such a borrow are never part of a detected deadlock.
+pub fn disown(cell: usize) {
+    let thread = thread::current().id();
+    let mut graph = GRAPH.lock();
+
+    if let Some(holder) = graph
+        .holders
+        .iter_mut()
+        .rev()
+        .find(|&&mut held| held == (cell, Some(thread)))
+    {
+        holder.1 = None;
+    }
+}
+
+/// Record that the current thread waits for the cell at address `cell`.
+///
+/// Fails if this completes a cycle, in which case the current thread is not recorded as waiting.
+pub fn start_waiting(cell: usize) -> Result<(), BorrowStateErr> {
+    let thread = thread::current().id();
+    let mut graph = GRAPH.lock();
+
+    graph.stop_waiting(thread);
+    graph.waiting.push((thread, cell));
+
+    match graph.find_cycle(thread) {
+        Some(cycle) => {
+            graph.stop_waiting(thread);
+
+            Err(BorrowStateErr::Deadlock {
+                threads: cycle.iter().map(|(thread, _)| *thread).collect(),
+                cells: cycle.iter().map(|(_, cell)| *cell).collect(),
+            })
+        }
+        None => Ok(()),
+    }
+}
+
+/// Record that the current thread no longer waits for any cell.
+pub fn stop_waiting() {
+    GRAPH.lock().stop_waiting(thread::current().id());
+}
+
+#[cfg(test)]
+mod test {
+    use core::pin::pin;
+    use std::string::ToString;
+    use std::sync::Barrier;
+
+    use super::*;
+    use crate::{BorrowErr, GdCell};
+
+    #[test]
+    fn waiting_for_own_borrow_is_a_deadlock() {
+        let cell = pin!(GdCell::new(0));
+        let cell = cell.into_ref();
+
+        let _shared = cell.gd_ref().unwrap();
+        let err = cell.gd_mut_blocking().map(|_| ()).unwrap_err();
+        let err = err.downcast_ref::<BorrowErr>().unwrap();
+
+        assert_eq!(
+            err.error,
+            BorrowStateErr::Deadlock {
+                threads: [thread::current().id()].into(),
+                cells: [core::ptr::from_ref(cell.get_ref()).addr()].into(),
+            }
+        );
+    }
+
+    #[test]
+    fn crossed_borrows_are_a_deadlock() {
+        let a = pin!(GdCell::new(0));
+        let a = a.into_ref();
+        let b = pin!(GdCell::new(0));
+        let b = b.into_ref();
+
+        let both_bound = Barrier::new(2);
+        let results = thread::scope(|scope| {
+            let first = scope.spawn(|| {
+                let _a = a.gd_mut().unwrap();
+                both_bound.wait();
+                b.gd_mut_blocking()
+                    .map(|_| ())
+                    .map_err(|err| err.to_string())
+            });
+            let second = scope.spawn(|| {
+                let _b = b.gd_mut().unwrap();
+                both_bound.wait();
+                a.gd_mut_blocking()
+                    .map(|_| ())
+                    .map_err(|err| err.to_string())
+            });
+
+            [first.join().unwrap(), second.join().unwrap()]
+        });
+
+        // Whichever thread waits last detects the deadlock and releases its borrow, so the other one then
+        // succeeds.
+        let errors = results.iter().filter_map(|result| result.as_ref().err());
+        let errors = errors.collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1, "{results:?}");
+        assert!(errors[0].contains("deadlock detected"), "{}", errors[0]);
+    }
+
+    #[test]
+    fn arc_guard_moved_to_another_thread_is_waited_for() {
+        use alloc::sync::Arc;
+        use std::time::Duration;
+
+        let cell = Arc::pin(GdCell::new(0));
+        let mut guard = cell.gd_mut_arc().unwrap();
+        let releasing = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            *guard += 1;
+        });
+
+        // The borrow is released by the other thread, so waiting for it is no deadlock.
+        assert_eq!(*cell.as_ref().gd_mut_blocking().unwrap(), 1);
+        releasing.join().unwrap();
+    }
+}
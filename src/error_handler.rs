@@ -0,0 +1,99 @@
+//! A global handler which is called whenever borrowing a [`GdCell`](crate::GdCell) fails.
+//!
+//! This gives an integration a single place to report every borrow failure, for instance as an engine error
+//! print, before the `Err` is returned to the caller.
+
+use core::panic::Location;
+
+use crate::borrow_state::BorrowStateErr;
+use crate::lock::{DefaultLock, Lock};
+
+/// A function called with the error, the name of the type stored in the cell and the location of the failed
+/// borrow.
+pub type BorrowErrorHandler = fn(&BorrowStateErr, &'static str, &'static Location<'static>);
+
+static HANDLER: DefaultLock<Option<BorrowErrorHandler>> = DefaultLock::new(None);
+
+/// Set the handler which is called whenever [`GdCell::gd_ref`](crate::GdCell::gd_ref),
+/// [`GdCell::gd_mut`](crate::GdCell::gd_mut), [`GdCell::gd_upgradable`](crate::GdCell::gd_upgradable),
+/// [`GdUpgradable::upgrade`](crate::GdUpgradable::upgrade) or
+/// [`GdCell::set_non_aliasing`](crate::GdCell::set_non_aliasing) fail, replacing any previously set handler.
+///
+/// The handler is called on the thread doing the borrow, after the cell's internal locks are released.
+pub fn set_borrow_error_handler(handler: BorrowErrorHandler) {
+    *HANDLER.lock() = Some(handler);
+}
+
+/// Remove the handler set with [`set_borrow_error_handler`].
+pub fn remove_borrow_error_handler() {
+    *HANDLER.lock() = None;
+}
+
+/// Pass `err` to the handler, attributed to `location`.
+pub(crate) fn report<T>(err: &BorrowStateErr, location: &'static Location<'static>) {
+    // Copy the handler out, so it may replace itself.
+    let handler = *HANDLER.lock();
+
+    if let Some(handler) = handler {
+        handler(err, core::any::type_name::<T>(), location);
+    }
+}
+
+/// Held by tests which set the handler, since it is shared by all tests running at the same time.
+#[cfg(all(test, feature = "std"))]
+pub(crate) static TEST_HANDLER_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use core::pin::pin;
+    use std::string::{String, ToString};
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::GdCell;
+
+    /// Only stored in the cell of this test, so failures of concurrently running tests can be told apart.
+    struct Marker;
+
+    static FAILURES: Mutex<Vec<(String, u32)>> = Mutex::new(Vec::new());
+
+    fn record_failure(
+        err: &BorrowStateErr,
+        type_name: &'static str,
+        location: &'static Location<'static>,
+    ) {
+        if type_name.ends_with("Marker") {
+            FAILURES
+                .lock()
+                .unwrap()
+                .push((err.to_string(), location.line()));
+        }
+    }
+
+    #[test]
+    fn handler_sees_failures() {
+        let _handler = TEST_HANDLER_LOCK.lock().unwrap();
+        set_borrow_error_handler(record_failure);
+
+        let cell = pin!(GdCell::new(Marker));
+        let cell = cell.into_ref();
+
+        let mut guard = cell.gd_mut().unwrap();
+        let line = line!() + 1;
+        assert!(cell.gd_ref().is_err());
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard).unwrap();
+        drop(cell.gd_ref().unwrap());
+        drop(no_alias_guard);
+        drop(guard);
+
+        remove_borrow_error_handler();
+        assert!(cell.gd_upgradable().is_ok());
+
+        let failures = FAILURES.lock().unwrap();
+        assert_eq!(
+            *failures,
+            [(BorrowStateErr::HasAliasingRef.to_string(), line)]
+        );
+    }
+}
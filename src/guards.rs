@@ -1,42 +1,82 @@
-use std::{
+use core::{
+    fmt,
+    mem::ManuallyDrop,
     ops::{Deref, DerefMut},
+    panic::Location,
     ptr::NonNull,
-    sync::Mutex,
 };
 
+use crate::borrow_err::BorrowErr;
 use crate::borrow_state::BorrowState;
+use crate::cell_state::CellState;
+use crate::hooks::{BorrowEventKind, EventSource};
+use crate::lock::{DefaultLockFamily, Lock, LockFamily};
+use crate::ptr_stack::PtrStack;
+use crate::DEFAULT_REENTRANCY_DEPTH;
 
-#[derive(Debug)]
-pub struct NonAliasingGuard<'a, T> {
-    state: &'a Mutex<BorrowState>,
-    current_ptr: &'a Mutex<Vec<NonNull<T>>>,
+pub struct NonAliasingGuard<
+    'a,
+    T,
+    const DEPTH: usize = DEFAULT_REENTRANCY_DEPTH,
+    L: LockFamily = DefaultLockFamily,
+> {
+    state: &'a L::Lock<CellState>,
+    current_ptr: &'a L::Lock<PtrStack<T, DEPTH>>,
+    source: EventSource,
 }
 
-impl<'a, T> NonAliasingGuard<'a, T> {
-    pub fn new(state: &'a Mutex<BorrowState>, current_ptr: &'a Mutex<Vec<NonNull<T>>>) -> Self {
-        Self { state, current_ptr }
+impl<'a, T, const DEPTH: usize, L: LockFamily> NonAliasingGuard<'a, T, DEPTH, L> {
+    pub fn new(
+        state: &'a L::Lock<CellState>,
+        current_ptr: &'a L::Lock<PtrStack<T, DEPTH>>,
+        source: EventSource,
+    ) -> Self {
+        Self {
+            state,
+            current_ptr,
+            source,
+        }
     }
 }
 
-impl<'a, T> Drop for NonAliasingGuard<'a, T> {
+impl<'a, T, const DEPTH: usize, L: LockFamily> fmt::Debug for NonAliasingGuard<'a, T, DEPTH, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NonAliasingGuard")
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, T, const DEPTH: usize, L: LockFamily> Drop for NonAliasingGuard<'a, T, DEPTH, L> {
     fn drop(&mut self) {
-        let Self { state, current_ptr } = self;
-        let mut state_guard = state.lock().unwrap();
-        let mut ptr_guard = current_ptr.lock().unwrap();
-        state_guard.unset_non_aliasing().unwrap();
+        let Self {
+            state,
+            current_ptr,
+            source,
+        } = self;
+        let mut state_guard = state.lock();
+        let mut ptr_guard = current_ptr.lock();
+        let mut result = state_guard.borrow.unset_non_aliasing();
+        let event = source.record(
+            BorrowEventKind::UnsetNonAliasing,
+            &mut state_guard,
+            result.as_mut().err(),
+        );
+        result.unwrap();
         ptr_guard.pop().unwrap();
         drop(state_guard);
         drop(ptr_guard);
+        event.emit();
     }
 }
 
-#[derive(Debug)]
-pub struct GdRef<'a, T> {
-    state: &'a Mutex<BorrowState>,
+pub struct GdRef<'a, T, L: LockFamily = DefaultLockFamily> {
+    state: &'a L::Lock<CellState>,
     value: NonNull<T>,
+    source: EventSource,
 }
 
-impl<'a, T> GdRef<'a, T> {
+impl<'a, T, L: LockFamily> GdRef<'a, T, L> {
     /// Create a new `GdRef` guard which can be immutably dereferenced.
     ///
     /// # Safety
@@ -44,12 +84,20 @@ impl<'a, T> GdRef<'a, T> {
     /// The value behind the `value` pointer must be accessible for as long as the guard is not dropped.
     /// And there must also be no mutable references made to the value for as long as this guard exists, nor
     /// can this alias any existing mutable references.
-    pub unsafe fn new(state: &'a Mutex<BorrowState>, value: NonNull<T>) -> Self {
-        Self { state, value }
+    pub unsafe fn new(
+        state: &'a L::Lock<CellState>,
+        value: NonNull<T>,
+        source: EventSource,
+    ) -> Self {
+        Self {
+            state,
+            value,
+            source,
+        }
     }
 }
 
-impl<'a, T> Deref for GdRef<'a, T> {
+impl<'a, T, L: LockFamily> Deref for GdRef<'a, T, L> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -57,20 +105,97 @@ impl<'a, T> Deref for GdRef<'a, T> {
     }
 }
 
-impl<'a, T> Drop for GdRef<'a, T> {
+impl<'a, T, L: LockFamily> Drop for GdRef<'a, T, L> {
     fn drop(&mut self) {
-        self.state.lock().unwrap().decrement_shared().unwrap();
+        self.source
+            .apply(
+                BorrowEventKind::ReleaseShared,
+                self.state,
+                BorrowState::decrement_shared,
+            )
+            .unwrap();
     }
 }
 
-#[derive(Debug)]
-pub struct GdMut<'a, T> {
-    state: &'a Mutex<BorrowState>,
+pub struct GdUpgradable<'a, T, L: LockFamily = DefaultLockFamily> {
+    state: &'a L::Lock<CellState>,
+    value: NonNull<T>,
+    source: EventSource,
+}
+
+impl<'a, T, L: LockFamily> GdUpgradable<'a, T, L> {
+    /// Create a new `GdUpgradable` guard which can be immutably dereferenced, and later upgraded into a
+    /// [`GdMut`].
+    ///
+    /// # Safety
+    ///
+    /// The value behind the `value` pointer must be accessible for as long as the guard is not dropped.
+    /// And there must also be no mutable references made to the value for as long as this guard exists, nor
+    /// can this alias any existing mutable references. Once upgraded, `value` must be valid to use as
+    /// described in [`GdMut::new`].
+    pub unsafe fn new(
+        state: &'a L::Lock<CellState>,
+        value: NonNull<T>,
+        source: EventSource,
+    ) -> Self {
+        Self {
+            state,
+            value,
+            source,
+        }
+    }
+
+    /// Upgrade this guard into a guard which can be mutably dereferenced.
+    ///
+    /// Fails if there are other shared borrows, in which case the guard is returned unchanged together with
+    /// the error, which is also passed to the borrow error handler.
+    #[track_caller]
+    #[allow(clippy::result_large_err)]
+    pub fn upgrade(self) -> Result<GdMut<'a, T, L>, (Self, BorrowErr)> {
+        let kind = BorrowEventKind::Upgrade;
+        let source = self.source.at_caller();
+        let count = match source.apply(kind, self.state, BorrowState::upgrade) {
+            Ok(count) => count,
+            Err(failure) => return Err((self, failure.report::<T>(kind, Location::caller()))),
+        };
+
+        let this = ManuallyDrop::new(self);
+
+        // SAFETY:
+        // `upgrade` succeeded, therefore this was the only shared reference and there are no aliasing mutable
+        // references. The borrow state now tracks this as a mutable reference instead.
+        unsafe { Ok(GdMut::new(this.state, count, this.value, source)) }
+    }
+}
+
+impl<'a, T, L: LockFamily> Deref for GdUpgradable<'a, T, L> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<'a, T, L: LockFamily> Drop for GdUpgradable<'a, T, L> {
+    fn drop(&mut self) {
+        self.source
+            .apply(
+                BorrowEventKind::ReleaseUpgradable,
+                self.state,
+                BorrowState::decrement_upgradable,
+            )
+            .unwrap();
+    }
+}
+
+pub struct GdMut<'a, T, L: LockFamily = DefaultLockFamily> {
+    state: &'a L::Lock<CellState>,
     count: usize,
     value: NonNull<T>,
+    source: EventSource,
 }
 
-impl<'a, T> GdMut<'a, T> {
+impl<'a, T, L: LockFamily> GdMut<'a, T, L> {
     /// Create a new `GdMut` guard which can be mutably dereferenced.
     ///
     /// # Safety
@@ -80,20 +205,26 @@ impl<'a, T> GdMut<'a, T> {
     /// guard exists, unless:
     /// 1. It is know that this guard cannot be used to make a new reference when those references exist.
     /// 2. Any new references to the same value must be derived from the same `value` pointer.
-    pub unsafe fn new(state: &'a Mutex<BorrowState>, count: usize, value: NonNull<T>) -> Self {
+    pub unsafe fn new(
+        state: &'a L::Lock<CellState>,
+        count: usize,
+        value: NonNull<T>,
+        source: EventSource,
+    ) -> Self {
         Self {
             state,
             count,
             value,
+            source,
         }
     }
 }
 
-impl<'a, T> Deref for GdMut<'a, T> {
+impl<'a, T, L: LockFamily> Deref for GdMut<'a, T, L> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        let count = self.state.lock().unwrap().mut_count();
+        let count = self.state.lock().borrow.mut_count();
         // This is just a best-effort error check. It should never be triggered.
         assert_eq!(
             self.count, count,
@@ -103,9 +234,9 @@ impl<'a, T> Deref for GdMut<'a, T> {
     }
 }
 
-impl<'a, T> DerefMut for GdMut<'a, T> {
+impl<'a, T, L: LockFamily> DerefMut for GdMut<'a, T, L> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        let count = self.state.lock().unwrap().mut_count();
+        let count = self.state.lock().borrow.mut_count();
         // This is just a best-effort error check. It should never be triggered.
         assert_eq!(
             self.count, count,
@@ -115,8 +246,33 @@ impl<'a, T> DerefMut for GdMut<'a, T> {
     }
 }
 
-impl<'a, T> Drop for GdMut<'a, T> {
+impl<'a, T, L: LockFamily> Drop for GdMut<'a, T, L> {
     fn drop(&mut self) {
-        self.state.lock().unwrap().decrement_mut().unwrap();
+        self.source
+            .apply(
+                BorrowEventKind::ReleaseMut,
+                self.state,
+                BorrowState::decrement_mut,
+            )
+            .unwrap();
     }
 }
+
+/// Forward `Debug` and `Display` of a guard to the borrowed value.
+macro_rules! impl_fmt_for_guard {
+    ($($guard:ident),*) => {$(
+        impl<'a, T: fmt::Debug, L: LockFamily> fmt::Debug for $guard<'a, T, L> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&**self, f)
+            }
+        }
+
+        impl<'a, T: fmt::Display, L: LockFamily> fmt::Display for $guard<'a, T, L> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&**self, f)
+            }
+        }
+    )*};
+}
+
+impl_fmt_for_guard!(GdRef, GdUpgradable, GdMut);
@@ -0,0 +1,131 @@
+use alloc::{boxed::Box, sync::Arc};
+use core::{error::Error, fmt, pin::Pin};
+
+use crate::guards::{GdMut, GdRef, GdUpgradable, NonAliasingGuard};
+use crate::lock::{DefaultLockFamily, LockFamily};
+use crate::{GdCell, DEFAULT_REENTRANCY_DEPTH};
+
+/// A cloneable handle to a [`GdCell`] in a pinned shared allocation.
+///
+/// This exposes the borrows of the cell without the caller ever handling a [`Pin`]. Cloning the handle is
+/// cheap and clones refer to the same cell, which is freed when the last handle is dropped.
+pub struct GdHandle<
+    T,
+    const DEPTH: usize = DEFAULT_REENTRANCY_DEPTH,
+    L: LockFamily = DefaultLockFamily,
+> {
+    cell: Pin<Arc<GdCell<T, DEPTH, L>>>,
+}
+
+impl<T> GdHandle<T> {
+    /// Move `value` into a new cell and return a handle to it.
+    pub fn new(value: T) -> Self {
+        Self {
+            cell: Arc::pin(GdCell::new(value)),
+        }
+    }
+}
+
+impl<T, const DEPTH: usize, L: LockFamily> GdHandle<T, DEPTH, L> {
+    /// Returns the cell this handle refers to.
+    pub fn cell(&self) -> Pin<&GdCell<T, DEPTH, L>> {
+        self.cell.as_ref()
+    }
+
+    /// Take a shared borrow of the value, see [`GdCell::gd_ref`].
+    #[track_caller]
+    pub fn bind(&self) -> Result<GdRef<'_, T, L>, Box<dyn Error>> {
+        self.cell().gd_ref()
+    }
+
+    /// Take a mutable borrow of the value, see [`GdCell::gd_mut`].
+    #[track_caller]
+    pub fn bind_mut(&self) -> Result<GdMut<'_, T, L>, Box<dyn Error>> {
+        self.cell().gd_mut()
+    }
+
+    /// Take an upgradable borrow of the value, see [`GdCell::gd_upgradable`].
+    #[track_caller]
+    pub fn bind_upgradable(&self) -> Result<GdUpgradable<'_, T, L>, Box<dyn Error>> {
+        self.cell().gd_upgradable()
+    }
+
+    /// Mark the current mutable borrow as non-aliasing, see [`GdCell::set_non_aliasing`].
+    #[track_caller]
+    pub fn set_non_aliasing<'b>(
+        &'b self,
+        current_ref: &'b mut T,
+    ) -> Result<NonAliasingGuard<'b, T, DEPTH, L>, Box<dyn Error>> {
+        self.cell().set_non_aliasing(current_ref)
+    }
+
+    /// Mark the current mutable borrow as non-aliasing while only allowing new shared borrows, see
+    /// [`GdCell::set_non_aliasing_readonly`].
+    #[track_caller]
+    pub fn set_non_aliasing_readonly<'b>(
+        &'b self,
+        current_ref: &'b mut T,
+    ) -> Result<NonAliasingGuard<'b, T, DEPTH, L>, Box<dyn Error>> {
+        self.cell().set_non_aliasing_readonly(current_ref)
+    }
+
+    /// Returns `true` if both handles refer to the same cell.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        core::ptr::eq(&*self.cell, &*other.cell)
+    }
+}
+
+impl<T, const DEPTH: usize, L: LockFamily> Clone for GdHandle<T, DEPTH, L> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: Pin::clone(&self.cell),
+        }
+    }
+}
+
+impl<T, const DEPTH: usize, L: LockFamily> From<Pin<Arc<GdCell<T, DEPTH, L>>>>
+    for GdHandle<T, DEPTH, L>
+{
+    fn from(cell: Pin<Arc<GdCell<T, DEPTH, L>>>) -> Self {
+        Self { cell }
+    }
+}
+
+impl<T, const DEPTH: usize, L: LockFamily> From<GdHandle<T, DEPTH, L>>
+    for Pin<Arc<GdCell<T, DEPTH, L>>>
+{
+    fn from(handle: GdHandle<T, DEPTH, L>) -> Self {
+        handle.cell
+    }
+}
+
+impl<T: fmt::Debug, const DEPTH: usize, L: LockFamily> fmt::Debug for GdHandle<T, DEPTH, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.cell, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn handle_binds_without_pin() {
+        let handle = GdHandle::new(1);
+        let other = handle.clone();
+        assert!(handle.ptr_eq(&other));
+        assert!(!handle.ptr_eq(&GdHandle::new(1)));
+
+        let mut guard = handle.bind_mut().unwrap();
+        assert!(other.bind().is_err());
+
+        let no_alias_guard = handle.set_non_aliasing(&mut guard).unwrap();
+        *other.bind_mut().unwrap() += 1;
+        assert_eq!(*other.bind().unwrap(), 2);
+        drop(no_alias_guard);
+
+        *guard += 1;
+        drop(guard);
+        assert_eq!(*other.bind().unwrap(), 3);
+    }
+}
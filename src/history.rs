@@ -0,0 +1,141 @@
+//! A per-cell record of the most recent borrow transitions, for post-mortem debugging.
+//!
+//! Only available with the `history` feature, and only recorded for cells which opted in with
+//! [`GdCell::enable_history`](crate::GdCell::enable_history).
+
+use alloc::{collections::VecDeque, string::String};
+use core::{fmt, panic::Location};
+
+use crate::{borrow_state::BorrowStateErr, hooks::BorrowEventKind};
+
+/// A single borrow transition recorded in the history of a [`GdCell`](crate::GdCell).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HistoryEntry {
+    /// What kind of transition was attempted.
+    pub kind: BorrowEventKind,
+    /// The error the transition failed with, if any.
+    pub error: Option<BorrowStateErr>,
+    /// The thread which attempted the transition.
+    #[cfg(feature = "std")]
+    pub thread: std::thread::ThreadId,
+    /// Where the borrow was taken. For releases, this is where the released borrow was taken.
+    pub location: &'static Location<'static>,
+}
+
+impl fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} at {}", self.kind, self.location)?;
+
+        #[cfg(feature = "std")]
+        write!(f, " on {:?}", self.thread)?;
+
+        match &self.error {
+            Some(err) => write!(f, ": failed with `{err}`"),
+            None => write!(f, ": ok"),
+        }
+    }
+}
+
+/// A ring buffer of the most recent borrow transitions of a cell.
+#[derive(Debug, Clone)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Record a new entry, forgetting the oldest one if the history is full.
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(entry);
+    }
+
+    /// Returns the recorded entries, from oldest to newest.
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+
+    /// Append every entry to `out`, one per line.
+    pub fn dump(&self, out: &mut String) {
+        use core::fmt::Write;
+
+        for entry in self.entries() {
+            // Writing to a `String` cannot fail.
+            _ = write!(out, "\n  {entry}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::{string::ToString, vec::Vec};
+    use core::pin::pin;
+
+    use super::*;
+    use crate::borrow_state::BorrowState;
+    use crate::{cell_state::CellState, hooks::EventSource, GdCell};
+
+    #[test]
+    fn history_keeps_most_recent() {
+        use BorrowEventKind as Kind;
+
+        let cell = pin!(GdCell::new(0));
+        let cell = cell.into_ref();
+        assert!(cell.borrow_history().is_empty());
+
+        cell.enable_history(3);
+        let mut guard = cell.gd_mut().unwrap();
+        assert!(cell.gd_ref().is_err());
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard).unwrap();
+        drop(no_alias_guard);
+        drop(guard);
+
+        let history = cell.borrow_history();
+        let kinds = history.iter().map(|entry| entry.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                Kind::SetNonAliasing,
+                Kind::UnsetNonAliasing,
+                Kind::ReleaseMut
+            ]
+        );
+        assert!(history.iter().all(|entry| entry.error.is_none()));
+        assert!(history[0].location.file().ends_with("history.rs"));
+
+        cell.disable_history();
+        assert!(cell.borrow_history().is_empty());
+    }
+
+    #[test]
+    fn history_is_dumped_with_poison() {
+        let mut state = CellState::new(BorrowState::new());
+        state.history = Some(History::new(4));
+
+        let source = EventSource::new::<i32>(0);
+        _ = source.record(BorrowEventKind::Mut, &mut state, None);
+
+        let mut err = BorrowStateErr::Poisoned("invariant broken".into());
+        _ = source.record(BorrowEventKind::ReleaseMut, &mut state, Some(&mut err));
+
+        let message = err.to_string();
+        assert!(message.contains("invariant broken"));
+        assert!(message.contains("Mut at "));
+        assert!(message.contains("ReleaseMut at "));
+    }
+}
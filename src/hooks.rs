@@ -0,0 +1,387 @@
+//! Hooks for observing every borrow transition of every [`GdCell`](crate::GdCell).
+//!
+//! Only available with the `hooks` feature. Without it and the `history` feature, [`EventSource`] is
+//! zero-sized and recording an event compiles down to nothing.
+
+use core::panic::Location;
+
+#[cfg(feature = "std")]
+use crate::blocking;
+use crate::borrow_err::Failure;
+use crate::borrow_state::{BorrowState, BorrowStateErr};
+use crate::cell_state::CellState;
+#[cfg(feature = "deadlock-detection")]
+use crate::deadlock;
+#[cfg(feature = "history")]
+use crate::history::HistoryEntry;
+#[cfg(feature = "hooks")]
+use crate::lock::DefaultLock;
+use crate::lock::Lock;
+use crate::status::BorrowStatus;
+
+/// The kind of borrow transition, such as taking or releasing a mutable borrow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorrowEventKind {
+    /// A shared borrow was taken.
+    Shared,
+    /// A shared borrow was released.
+    ReleaseShared,
+    /// An upgradable borrow was taken.
+    Upgradable,
+    /// An upgradable borrow was released without being upgraded.
+    ReleaseUpgradable,
+    /// An upgradable borrow was upgraded into a mutable borrow.
+    Upgrade,
+    /// A mutable borrow was taken.
+    Mut,
+    /// A mutable borrow was released.
+    ReleaseMut,
+    /// A mutable borrow was marked as non-aliasing.
+    SetNonAliasing,
+    /// A mutable borrow was marked as non-aliasing, only allowing shared reentrant borrows.
+    SetNonAliasingReadOnly,
+    /// A mutable borrow was unmarked as non-aliasing.
+    UnsetNonAliasing,
+    /// The borrow state of the cell was poisoned.
+    Poisoned,
+}
+
+impl BorrowEventKind {
+    /// Returns `true` if this transition takes a new borrow, or makes an existing one more permissive.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn is_acquire(self) -> bool {
+        matches!(
+            self,
+            Self::Shared
+                | Self::Upgradable
+                | Self::Upgrade
+                | Self::Mut
+                | Self::SetNonAliasing
+                | Self::SetNonAliasingReadOnly
+        )
+    }
+
+    /// Returns `true` if this transition releases a borrow, which may let a blocked borrow continue.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn is_release(self) -> bool {
+        matches!(
+            self,
+            Self::ReleaseShared
+                | Self::ReleaseUpgradable
+                | Self::ReleaseMut
+                | Self::UnsetNonAliasing
+        )
+    }
+}
+
+/// A borrow transition of a [`GdCell`](crate::GdCell), passed to [`BorrowHook::on_borrow_event`].
+#[cfg(feature = "hooks")]
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct BorrowEvent<'a> {
+    /// What kind of transition was attempted.
+    pub kind: BorrowEventKind,
+    /// The address of the cell.
+    pub cell: usize,
+    /// The name of the type stored in the cell.
+    pub type_name: &'static str,
+    /// The borrows of the cell after the transition.
+    pub status: BorrowStatus,
+    /// Where the borrow was taken. For releases, this is where the released borrow was taken.
+    pub location: &'static Location<'static>,
+    /// The error the transition failed with, if any.
+    pub error: Option<&'a BorrowStateErr>,
+}
+
+/// A hook which is called on every borrow transition of every [`GdCell`](crate::GdCell).
+///
+/// The hook is called after the cell's internal locks are released, so it may inspect the cell. It is
+/// called on the thread doing the transition.
+#[cfg(feature = "hooks")]
+pub trait BorrowHook: Sync {
+    fn on_borrow_event(&self, event: &BorrowEvent<'_>);
+}
+
+#[cfg(feature = "hooks")]
+static HOOK: DefaultLock<Option<&'static dyn BorrowHook>> = DefaultLock::new(None);
+
+/// Set the hook which is called on every borrow transition, replacing any previously set hook.
+#[cfg(feature = "hooks")]
+pub fn set_borrow_hook(hook: &'static dyn BorrowHook) {
+    *HOOK.lock() = Some(hook);
+}
+
+/// Remove the hook set with [`set_borrow_hook`].
+#[cfg(feature = "hooks")]
+pub fn remove_borrow_hook() {
+    *HOOK.lock() = None;
+}
+
+/// The cell, type and caller location a borrow transition is attributed to.
+#[derive(Debug, Clone, Copy)]
+pub struct EventSource {
+    #[cfg(any(feature = "hooks", feature = "deadlock-detection"))]
+    cell: usize,
+    #[cfg(feature = "hooks")]
+    type_name: &'static str,
+    #[cfg(any(feature = "hooks", feature = "history"))]
+    location: &'static Location<'static>,
+}
+
+impl EventSource {
+    /// Attribute transitions to the cell at address `cell` storing a `T`, and to the caller.
+    #[cfg_attr(
+        not(any(feature = "hooks", feature = "deadlock-detection")),
+        allow(unused_variables)
+    )]
+    #[cfg_attr(not(feature = "hooks"), allow(clippy::extra_unused_type_parameters))]
+    #[track_caller]
+    #[inline]
+    pub fn new<T>(cell: usize) -> Self {
+        Self {
+            #[cfg(any(feature = "hooks", feature = "deadlock-detection"))]
+            cell,
+            #[cfg(feature = "hooks")]
+            type_name: core::any::type_name::<T>(),
+            #[cfg(any(feature = "hooks", feature = "history"))]
+            location: Location::caller(),
+        }
+    }
+
+    /// Attribute transitions to the same cell, but to the caller.
+    #[track_caller]
+    #[inline]
+    pub fn at_caller(self) -> Self {
+        self.at(Location::caller())
+    }
+
+    /// Attribute transitions to the same cell, but to `location`.
+    #[cfg_attr(
+        not(any(feature = "hooks", feature = "history")),
+        allow(unused_mut, unused_variables)
+    )]
+    #[inline]
+    pub fn at(mut self, location: &'static Location<'static>) -> Self {
+        #[cfg(any(feature = "hooks", feature = "history"))]
+        {
+            self.location = location;
+        }
+
+        self
+    }
+
+    /// Lock `state`, apply `op` to its borrows and record the transition. The transition is reported to the
+    /// hook once the lock is released.
+    #[inline]
+    pub fn apply<R>(
+        &self,
+        kind: BorrowEventKind,
+        state: &impl Lock<CellState>,
+        op: impl FnOnce(&mut BorrowState) -> Result<R, BorrowStateErr>,
+    ) -> Result<R, Failure> {
+        let mut guard = state.lock();
+        let mut result = guard
+            .ensure_may_borrow(kind)
+            .and_then(|()| op(&mut guard.borrow));
+        let event = self.record(kind, &mut guard, result.as_mut().err());
+        let result = result.map_err(|error| Failure {
+            error,
+            status: BorrowStatus::new(&guard.borrow),
+        });
+        drop(guard);
+        event.emit();
+
+        result
+    }
+
+    /// Record a transition which resulted in `state` in its history, to be reported with
+    /// [`RecordedEvent::emit`] once the state's lock is released.
+    ///
+    /// If the transition poisoned the state, the history is appended to the error.
+    #[cfg_attr(not(feature = "hooks"), allow(unused_variables))]
+    #[inline]
+    pub fn record(
+        &self,
+        kind: BorrowEventKind,
+        state: &mut CellState,
+        error: Option<&mut BorrowStateErr>,
+    ) -> RecordedEvent {
+        state.update_reentrant_thread();
+
+        #[cfg(feature = "deadlock-detection")]
+        if error.is_none() {
+            deadlock::record_transition(self.cell, kind);
+        }
+
+        #[cfg(feature = "stats")]
+        {
+            state.stats.record(kind, &state.borrow, error.as_deref());
+            crate::stats::record_global(kind, &state.borrow, error.as_deref());
+        }
+
+        #[cfg(feature = "history")]
+        let error = self.record_history(kind, state, error);
+
+        RecordedEvent {
+            #[cfg(feature = "std")]
+            kind,
+            #[cfg(feature = "hooks")]
+            inner: HOOK.lock().map(|hook| {
+                (
+                    hook,
+                    *self,
+                    kind,
+                    BorrowStatus::new(&state.borrow),
+                    error.as_deref().cloned(),
+                )
+            }),
+        }
+    }
+
+    #[cfg(feature = "history")]
+    fn record_history<'e>(
+        &self,
+        kind: BorrowEventKind,
+        state: &mut CellState,
+        mut error: Option<&'e mut BorrowStateErr>,
+    ) -> Option<&'e mut BorrowStateErr> {
+        let Some(history) = &mut state.history else {
+            return error;
+        };
+
+        history.push(HistoryEntry {
+            kind,
+            error: error.as_deref().cloned(),
+            #[cfg(feature = "std")]
+            thread: std::thread::current().id(),
+            location: self.location,
+        });
+
+        if let Some(BorrowStateErr::Poisoned(message)) = error.as_deref_mut() {
+            message.push_str("\nmost recent borrows:");
+            history.dump(message);
+        }
+
+        error
+    }
+}
+
+/// A transition recorded by [`EventSource::record`] which has not yet been reported to the hook.
+#[must_use]
+pub struct RecordedEvent {
+    #[cfg(feature = "std")]
+    kind: BorrowEventKind,
+    #[cfg(feature = "hooks")]
+    #[allow(clippy::type_complexity)]
+    inner: Option<(
+        &'static dyn BorrowHook,
+        EventSource,
+        BorrowEventKind,
+        BorrowStatus,
+        Option<BorrowStateErr>,
+    )>,
+}
+
+impl RecordedEvent {
+    /// Report the transition to the hook, if there is one, and wake blocked borrows if it released a borrow.
+    #[inline]
+    pub fn emit(self) {
+        #[cfg(feature = "std")]
+        if self.kind.is_release() {
+            blocking::notify_released();
+        }
+
+        #[cfg(feature = "hooks")]
+        if let Some((hook, source, kind, status, error)) = self.inner {
+            let mut event = BorrowEvent {
+                kind,
+                cell: source.cell,
+                type_name: source.type_name,
+                status,
+                location: source.location,
+                error: error.as_ref(),
+            };
+            hook.on_borrow_event(&event);
+
+            if let Some(BorrowStateErr::Poisoned(_)) = error {
+                event.kind = BorrowEventKind::Poisoned;
+                hook.on_borrow_event(&event);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "hooks", feature = "std"))]
+mod test {
+    use core::pin::pin;
+    use std::sync::Mutex;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::GdCell;
+
+    /// The cell, kind, shared count, mutable count and whether it failed, for every event.
+    type Events = Vec<(usize, BorrowEventKind, usize, usize, bool)>;
+
+    struct RecordingHook {
+        events: Mutex<Events>,
+    }
+
+    impl BorrowHook for RecordingHook {
+        fn on_borrow_event(&self, event: &BorrowEvent<'_>) {
+            self.events.lock().unwrap().push((
+                event.cell,
+                event.kind,
+                event.status.shared_count,
+                event.status.mut_count,
+                event.error.is_some(),
+            ));
+        }
+    }
+
+    static RECORDING_HOOK: RecordingHook = RecordingHook {
+        events: Mutex::new(Vec::new()),
+    };
+
+    #[test]
+    fn hook_sees_transitions() {
+        use BorrowEventKind as Kind;
+
+        set_borrow_hook(&RECORDING_HOOK);
+
+        let cell = pin!(GdCell::new(0));
+        let cell = cell.into_ref();
+        let address = core::ptr::from_ref(cell.get_ref()).addr();
+
+        let mut guard = cell.gd_mut().unwrap();
+        assert!(cell.gd_ref().is_err());
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard).unwrap();
+        drop(cell.gd_ref().unwrap());
+        drop(no_alias_guard);
+        drop(guard);
+
+        remove_borrow_hook();
+
+        let events = RECORDING_HOOK
+            .events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(cell, ..)| *cell == address)
+            .map(|(_, kind, shared, mutable, failed)| (*kind, *shared, *mutable, *failed))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            events,
+            [
+                (Kind::Mut, 0, 1, false),
+                (Kind::Shared, 0, 1, true),
+                (Kind::SetNonAliasing, 0, 1, false),
+                (Kind::Shared, 1, 1, false),
+                (Kind::ReleaseShared, 0, 1, false),
+                (Kind::UnsetNonAliasing, 0, 1, false),
+                (Kind::ReleaseMut, 0, 0, false),
+            ]
+        );
+    }
+}
@@ -1,43 +1,246 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+mod arc_guards;
+#[cfg(feature = "std")]
+mod base;
+#[cfg(feature = "std")]
+mod blocking;
+mod borrow_err;
 mod borrow_state;
+#[cfg(feature = "std")]
+mod call;
+mod cell_state;
+#[cfg(feature = "std")]
+mod class_db;
+#[cfg(feature = "deadlock-detection")]
+mod deadlock;
+mod error_handler;
 mod guards;
+mod handle;
+#[cfg(feature = "history")]
+mod history;
+mod hooks;
+pub mod lock;
+mod many;
+mod policy;
+mod ptr_stack;
+#[cfg(feature = "std")]
+mod registry;
+#[cfg(feature = "stats")]
+mod stats;
+mod status;
+mod trace;
+
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    cell::UnsafeCell,
+    error::Error,
+    fmt,
+    marker::PhantomPinned,
+    panic::Location,
+    pin::Pin,
+    ptr::{self, NonNull},
+};
 
-use std::{
-    cell::UnsafeCell, error::Error, marker::PhantomPinned, pin::Pin, ptr::NonNull, sync::Mutex,
+pub use arc_guards::{ArcGdMut, ArcGdRef};
+#[cfg(feature = "std")]
+pub use base::{Base, BaseGuard, GdClass};
+pub use borrow_err::BorrowErr;
+use borrow_err::Failure;
+pub use borrow_state::{BorrowState, BorrowStateErr};
+#[cfg(feature = "std")]
+pub use call::{call_mut, call_ref, try_call_mut, try_call_ref, CallErr};
+use cell_state::CellState;
+#[cfg(feature = "std")]
+pub use class_db::{call_by_name, BindMode, ClassDb};
+pub use error_handler::{
+    remove_borrow_error_handler, set_borrow_error_handler, BorrowErrorHandler,
 };
+#[cfg(feature = "derive")]
+pub use gd_cell_derive::{gd_methods, GdClass};
+pub use guards::{GdMut, GdRef, GdUpgradable, NonAliasingGuard};
+pub use handle::GdHandle;
+#[cfg(feature = "history")]
+pub use history::HistoryEntry;
+pub use hooks::BorrowEventKind;
+use hooks::EventSource;
+#[cfg(feature = "hooks")]
+pub use hooks::{remove_borrow_hook, set_borrow_hook, BorrowEvent, BorrowHook};
+use lock::{DefaultLockFamily, Lock, LockFamily};
+pub use many::{gd_mut_many, gd_mut_pair};
+pub use policy::ReentrancyPolicy;
+use ptr_stack::PtrStack;
+#[cfg(feature = "std")]
+pub use registry::{
+    free_instance, instance, is_instance_alive, register_instance, register_instance_with,
+    register_ref_counted, register_ref_counted_with, try_instance, Instance, InstanceErr,
+    InstanceId, WeakInstance,
+};
+#[cfg(feature = "stats")]
+pub use stats::{global_borrow_stats, reset_global_borrow_stats, BorrowStats};
+pub use status::BorrowStatus;
+pub use trace::{BorrowOp, BorrowTrace, ParseTraceError};
 
-use borrow_state::BorrowState;
-pub use guards::{GdMut, GdRef, NonAliasingGuard};
+/// The default maximum number of nested [`GdCell::set_non_aliasing`] calls a [`GdCell`] supports.
+pub const DEFAULT_REENTRANCY_DEPTH: usize = 8;
 
-#[derive(Debug)]
-pub struct GdCell<T> {
-    state: Mutex<BorrowState>,
+/// A cell which allows reentrant mutable borrows, as long as the outer borrow is first marked as
+/// non-aliasing with [`GdCell::set_non_aliasing`].
+///
+/// `DEPTH` is the maximum number of nested non-aliasing borrows. Exceeding it makes
+/// [`GdCell::set_non_aliasing`] fail with [`BorrowStateErr::ReentrancyLimit`], like exceeding the
+/// `max_depth` of the [`ReentrancyPolicy`].
+///
+/// `L` is the [`LockFamily`] whose locks protect the borrow state, see the [`lock`] module.
+pub struct GdCell<
+    T,
+    const DEPTH: usize = DEFAULT_REENTRANCY_DEPTH,
+    L: LockFamily = DefaultLockFamily,
+> {
+    state: L::Lock<CellState>,
     value: UnsafeCell<T>,
-    current_ptr: Mutex<Vec<NonNull<T>>>,
+    current_ptr: L::Lock<PtrStack<T, DEPTH>>,
     _pin: PhantomPinned,
 }
 
+// SAFETY:
+// Pointers into the value are only stored while it is borrowed, and a borrowed cell cannot be moved. So moving
+// the cell to another thread only moves the value. The locks may be moved to another thread, as the
+// `LockFamily` guarantees.
+unsafe impl<T: Send, const DEPTH: usize, L: LockFamily> Send for GdCell<T, DEPTH, L> {}
+
+// SAFETY:
+// Shared borrows on several threads hand out `&T`, so `T` must be `Sync`. A mutable borrow hands out `&mut T` to
+// one thread at a time, so `T` must be `Send`. The borrow state is behind a lock, and reentrant borrows derived
+// from a non-aliasing borrow are only allowed on the thread which marked it, see `CellState::ensure_may_borrow`.
+// The `LockFamily` guarantees the locks exclude other threads.
+//
+// Without the `std` feature threads cannot be told apart, so the cell is not `Sync`.
+#[cfg(feature = "std")]
+unsafe impl<T: Send + Sync, const DEPTH: usize, L: LockFamily> Sync for GdCell<T, DEPTH, L> {}
+
 impl<T> GdCell<T> {
     pub fn new(value: T) -> Self {
+        Self::new_with_depth(value)
+    }
+
+    /// Create a new cell which is already pinned, so it can be borrowed with [`Pin::as_ref`].
+    ///
+    /// See also [`GdHandle`], which can be cloned and borrowed without handling a [`Pin`].
+    pub fn new_pinned(value: T) -> Pin<Box<Self>> {
+        Box::pin(Self::new(value))
+    }
+
+    /// Create a new cell which only allows the reentrant borrows permitted by `policy`.
+    pub fn with_policy(value: T, policy: ReentrancyPolicy) -> Self {
+        Self::new_with_depth_and_policy(value, policy)
+    }
+
+    /// Create a new cell which allows at most `max_depth` nested non-aliasing borrows.
+    ///
+    /// Exceeding this makes [`GdCell::gd_mut`] and [`GdCell::set_non_aliasing`] fail with
+    /// [`BorrowStateErr::ReentrancyLimit`]. The cell cannot support more than [`DEFAULT_REENTRANCY_DEPTH`]
+    /// nested borrows, so a larger `max_depth` is clamped to it. Use [`GdCell::new_with_depth_and_policy`]
+    /// for deeper nesting.
+    pub fn with_max_depth(value: T, max_depth: usize) -> Self {
+        let max_depth = max_depth.min(DEFAULT_REENTRANCY_DEPTH);
+        Self::with_policy(value, ReentrancyPolicy::Mutable { max_depth })
+    }
+}
+
+impl<T, const DEPTH: usize> GdCell<T, DEPTH> {
+    /// Create a new cell supporting at most `DEPTH` nested non-aliasing borrows.
+    pub fn new_with_depth(value: T) -> Self {
+        Self::new_with_depth_and_policy(value, ReentrancyPolicy::default())
+    }
+
+    /// Create a new cell supporting at most `DEPTH` nested non-aliasing borrows, which only allows the
+    /// reentrant borrows permitted by `policy`.
+    pub fn new_with_depth_and_policy(value: T, policy: ReentrancyPolicy) -> Self {
+        Self::new_with_lock(value, policy)
+    }
+}
+
+impl<T, const DEPTH: usize, L: LockFamily> GdCell<T, DEPTH, L> {
+    /// Create a new cell protected by the locks of `L`, supporting at most `DEPTH` nested non-aliasing
+    /// borrows, which only allows the reentrant borrows permitted by `policy`.
+    pub fn new_with_lock(value: T, policy: ReentrancyPolicy) -> Self {
         Self {
-            state: Mutex::new(BorrowState::new()),
+            state: L::Lock::new(CellState::new(BorrowState::with_policy(policy))),
             value: UnsafeCell::new(value),
-            current_ptr: Mutex::new(Vec::new()),
+            current_ptr: L::Lock::new(PtrStack::new()),
             _pin: PhantomPinned,
         }
     }
 
-    pub fn gd_ref(self: Pin<&Self>) -> Result<GdRef<'_, T>, Box<dyn Error>> {
-        self.state.lock().unwrap().increment_shared()?;
+    #[track_caller]
+    pub fn gd_ref(self: Pin<&Self>) -> Result<GdRef<'_, T, L>, Box<dyn Error>> {
+        let source = self.event_source();
+        Ok(self.check(BorrowEventKind::Shared, self.get_ref().try_ref(source))?)
+    }
+
+    /// Take a shared borrow, waiting for as long as another thread holds a conflicting borrow.
+    ///
+    /// Errors which waiting cannot resolve are returned immediately. Waiting for a borrow the current thread
+    /// holds itself, or for a cell held by a thread which in turn waits for the current thread, never ends.
+    /// With the `deadlock-detection` feature this fails with `BorrowStateErr::Deadlock` instead.
+    #[cfg(feature = "std")]
+    #[track_caller]
+    pub fn gd_ref_blocking(self: Pin<&Self>) -> Result<GdRef<'_, T, L>, Box<dyn Error>> {
+        let source = self.event_source();
+        let result = blocking::wait_for(self.address(), || self.get_ref().try_ref(source));
+
+        Ok(self.check(BorrowEventKind::Shared, result)?)
+    }
+
+    /// Take a shared borrow without requiring the cell to be pinned.
+    ///
+    /// This is sound because a shared borrow never stores a pointer into the cell. Any pointers that are
+    /// stored were stored by [`Self::set_non_aliasing`], which requires the cell to be pinned.
+    fn try_ref(&self, source: EventSource) -> Result<GdRef<'_, T, L>, Failure> {
+        source.apply(
+            BorrowEventKind::Shared,
+            &self.state,
+            BorrowState::increment_shared,
+        )?;
 
         // SAFETY:
         // `increment_shared` succeeded, therefore there cannot currently be any aliasing mutable references.
-        unsafe { Ok(GdRef::new(&self.get_ref().state, self.get_value())) }
+        unsafe { Ok(GdRef::new(&self.state, self.get_value(), source)) }
+    }
+
+    #[track_caller]
+    pub fn gd_mut(self: Pin<&Self>) -> Result<GdMut<'_, T, L>, Box<dyn Error>> {
+        let source = self.event_source();
+        Ok(self.check(BorrowEventKind::Mut, self.try_mut(source))?)
+    }
+
+    /// Take a mutable borrow, waiting for as long as another thread holds a conflicting borrow.
+    ///
+    /// Errors which waiting cannot resolve are returned immediately. Waiting for a borrow the current thread
+    /// holds itself, or for a cell held by a thread which in turn waits for the current thread, never ends.
+    /// With the `deadlock-detection` feature this fails with `BorrowStateErr::Deadlock` instead.
+    #[cfg(feature = "std")]
+    #[track_caller]
+    pub fn gd_mut_blocking(self: Pin<&Self>) -> Result<GdMut<'_, T, L>, Box<dyn Error>> {
+        let source = self.event_source();
+        let result = blocking::wait_for(self.address(), || self.try_mut(source));
+
+        Ok(self.check(BorrowEventKind::Mut, result)?)
     }
 
-    pub fn gd_mut(self: Pin<&Self>) -> Result<GdMut<'_, T>, Box<dyn Error>> {
-        let mut guard = self.state.lock().unwrap();
-        guard.increment_mut()?;
-        let count = guard.mut_count();
+    fn try_mut(self: Pin<&Self>, source: EventSource) -> Result<GdMut<'_, T, L>, Failure> {
+        let count = source
+            .apply(
+                BorrowEventKind::Mut,
+                &self.state,
+                BorrowState::increment_mut,
+            )
+            .map_err(|failure| failure.in_cell(self.address()))?;
 
         // SAFETY:
         // `increment_mut` succeeded, therefore any existing mutable references do not alias, and no new
@@ -54,60 +257,352 @@ impl<T> GdCell<T> {
         // We cannot pass in a different mutable reference, since `set_non_aliasing` ensures any references
         // matches the ones this one would return. And only one mutable reference to the same value can exist
         // since we cannot have any other aliasing mutable references around to pass in.
-        unsafe { Ok(GdMut::new(&self.get_ref().state, count, self.get_value())) }
+        unsafe {
+            Ok(GdMut::new(
+                &self.get_ref().state,
+                count,
+                self.get_value(),
+                source,
+            ))
+        }
     }
 
-    fn get_value(self: Pin<&Self>) -> NonNull<T> {
-        let mut ptr_vec = self.current_ptr.lock().unwrap();
+    /// Take a shared borrow which keeps the cell alive, so it is not tied to the lifetime of `self`.
+    ///
+    /// Fails in the same cases as [`Self::gd_ref`].
+    #[track_caller]
+    pub fn gd_ref_arc(self: &Pin<Arc<Self>>) -> Result<ArcGdRef<T, DEPTH, L>, Box<dyn Error>>
+    where
+        T: 'static,
+    {
+        ArcGdRef::new(self)
+    }
 
-        if ptr_vec.is_empty() {
-            ptr_vec.push(NonNull::new(self.value.get()).unwrap())
+    /// Take a mutable borrow which keeps the cell alive, so it is not tied to the lifetime of `self`.
+    ///
+    /// Fails in the same cases as [`Self::gd_mut`].
+    #[track_caller]
+    pub fn gd_mut_arc(self: &Pin<Arc<Self>>) -> Result<ArcGdMut<T, DEPTH, L>, Box<dyn Error>>
+    where
+        T: 'static,
+    {
+        ArcGdMut::new(self)
+    }
+
+    /// Take a shared borrow which may later be upgraded into a mutable borrow with [`GdUpgradable::upgrade`].
+    ///
+    /// This coexists with other shared borrows, but fails if there is a possibly aliasing mutable borrow or
+    /// another upgradable borrow.
+    #[track_caller]
+    pub fn gd_upgradable(self: Pin<&Self>) -> Result<GdUpgradable<'_, T, L>, Box<dyn Error>> {
+        let source = self.event_source();
+        self.check(
+            BorrowEventKind::Upgradable,
+            source.apply(
+                BorrowEventKind::Upgradable,
+                &self.state,
+                BorrowState::increment_upgradable,
+            ),
+        )?;
+
+        // SAFETY:
+        // `increment_upgradable` succeeded, therefore there cannot currently be any aliasing mutable references.
+        //
+        // Upgrading only succeeds when there are no other shared references left. At that point the pointer
+        // is used like the one from `gd_mut`, which is sound for the same reasons.
+        unsafe {
+            Ok(GdUpgradable::new(
+                &self.get_ref().state,
+                self.get_value(),
+                source,
+            ))
         }
+    }
 
-        *ptr_vec.last().unwrap()
+    /// Returns the pointer new borrows must be derived from.
+    ///
+    /// This is the pointer passed to the innermost [`Self::set_non_aliasing`] call, or a pointer to the value
+    /// itself if there is none.
+    fn get_value(&self) -> NonNull<T> {
+        self.current_ptr
+            .lock()
+            .last()
+            .unwrap_or_else(|| NonNull::new(self.value.get()).unwrap())
     }
 
     /// Set the current mutable borrow as not aliasing any other references.
     ///
-    /// Will error if there is no current possibly aliasing mutable borrow.
+    /// Will error if there is no current possibly aliasing mutable borrow, or if there already are `DEPTH`
+    /// non-aliasing borrows.
+    #[track_caller]
     pub fn set_non_aliasing<'a, 'b>(
         self: Pin<&'a Self>,
         current_ref: &'b mut T,
-    ) -> Result<NonAliasingGuard<'b, T>, Box<dyn Error>>
+    ) -> Result<NonAliasingGuard<'b, T, DEPTH, L>, Box<dyn Error>>
     where
         'a: 'b,
     {
-        let mut current_ptr_vec = self.current_ptr.lock().unwrap();
-        let current_ptr = *current_ptr_vec.last().unwrap();
+        self.push_non_aliasing(
+            current_ref,
+            BorrowEventKind::SetNonAliasing,
+            BorrowState::set_non_aliasing,
+        )
+    }
+
+    /// Set the current mutable borrow as not aliasing any other references, while only allowing new shared
+    /// borrows.
+    ///
+    /// Until the returned guard is dropped, [`Self::gd_ref`] can be used to reenter the cell but
+    /// [`Self::gd_mut`] fails with [`BorrowStateErr::IsReadOnly`].
+    ///
+    /// Will error in the same cases as [`Self::set_non_aliasing`].
+    #[track_caller]
+    pub fn set_non_aliasing_readonly<'a, 'b>(
+        self: Pin<&'a Self>,
+        current_ref: &'b mut T,
+    ) -> Result<NonAliasingGuard<'b, T, DEPTH, L>, Box<dyn Error>>
+    where
+        'a: 'b,
+    {
+        self.push_non_aliasing(
+            current_ref,
+            BorrowEventKind::SetNonAliasingReadOnly,
+            BorrowState::set_non_aliasing_readonly,
+        )
+    }
+
+    #[track_caller]
+    fn push_non_aliasing<'a, 'b>(
+        self: Pin<&'a Self>,
+        current_ref: &'b mut T,
+        kind: BorrowEventKind,
+        set_non_aliasing: fn(&mut BorrowState) -> Result<usize, BorrowStateErr>,
+    ) -> Result<NonAliasingGuard<'b, T, DEPTH, L>, Box<dyn Error>>
+    where
+        'a: 'b,
+    {
+        let current_ptr = self.get_value();
         let ptr = NonNull::from(current_ref);
 
         if current_ptr != ptr {
             // it is likely not unsound for this to happen, but it's unexpected
-            return Err("wrong reference passed in".into());
+            let failure = Failure {
+                error: "wrong reference passed in".into(),
+                status: self.borrow_status(),
+            };
+            self.check(kind, Err::<(), _>(failure))?;
         }
 
-        let mut state_guard = self.state.lock().unwrap();
-        state_guard.set_non_aliasing()?;
-        current_ptr_vec.push(ptr);
+        let source = self.event_source();
+        let mut state_guard = self.state.lock();
+        let mut ptr_stack = self.current_ptr.lock();
+
+        let mut result = if ptr_stack.is_full() {
+            Err(BorrowStateErr::ReentrancyLimit {
+                depth: DEPTH,
+                cell: Some(self.address()),
+            })
+        } else {
+            state_guard
+                .ensure_may_borrow(kind)
+                .and_then(|()| set_non_aliasing(&mut state_guard.borrow))
+                .map_err(|err| err.in_cell(self.address()))
+        };
+        let event = source.record(kind, &mut state_guard, result.as_mut().err());
+        let result = result.map_err(|error| Failure {
+            error,
+            status: BorrowStatus::new(&state_guard.borrow),
+        });
+
+        if result.is_ok() {
+            ptr_stack.push(ptr);
+        }
+
+        drop(ptr_stack);
         drop(state_guard);
-        drop(current_ptr_vec);
+        event.emit();
+        self.check(kind, result)?;
 
         Ok(NonAliasingGuard::new(
             &self.get_ref().state,
             &self.get_ref().current_ptr,
+            source,
         ))
     }
 
+    /// Attribute a failed borrow of `kind` to this cell, and report it to the borrow error handler.
+    #[track_caller]
+    fn check<R>(&self, kind: BorrowEventKind, result: Result<R, Failure>) -> Result<R, BorrowErr> {
+        self.check_at(kind, result, Location::caller())
+    }
+
+    /// Like [`Self::check`], but reports the failure at `location` instead of the caller.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    fn check_at<R>(
+        &self,
+        kind: BorrowEventKind,
+        result: Result<R, Failure>,
+        location: &'static Location<'static>,
+    ) -> Result<R, BorrowErr> {
+        result.map_err(|failure| failure.report::<T>(kind, location))
+    }
+
+    /// Returns the policy deciding which reentrant borrows this cell allows.
+    pub fn policy(&self) -> ReentrancyPolicy {
+        self.state.lock().borrow.policy()
+    }
+
+    /// Change the policy deciding which reentrant borrows this cell allows.
+    ///
+    /// Existing borrows are unaffected, but new borrows are checked against the new policy.
+    pub fn set_policy(&self, policy: ReentrancyPolicy) {
+        self.state.lock().borrow.set_policy(policy);
+    }
+
+    fn address(&self) -> usize {
+        ptr::from_ref(self).addr()
+    }
+
+    #[track_caller]
+    fn event_source(&self) -> EventSource {
+        EventSource::new::<T>(self.address())
+    }
+
+    /// Returns a snapshot of the current borrows of this cell.
+    pub fn borrow_status(&self) -> BorrowStatus {
+        BorrowStatus::new(&self.state.lock().borrow)
+    }
+
+    /// Start recording the last `capacity` borrow transitions of this cell.
+    ///
+    /// Any previously recorded transitions are discarded. The recorded transitions can be retrieved with
+    /// [`Self::borrow_history`], and are appended to the error if the borrow state is poisoned.
+    #[cfg(feature = "history")]
+    pub fn enable_history(&self, capacity: usize) {
+        self.state.lock().history = Some(history::History::new(capacity));
+    }
+
+    /// Stop recording borrow transitions of this cell, and discard the recorded ones.
+    #[cfg(feature = "history")]
+    pub fn disable_history(&self) {
+        self.state.lock().history = None;
+    }
+
+    /// Returns the most recent borrow transitions of this cell, from oldest to newest.
+    ///
+    /// This is empty unless recording was enabled with [`Self::enable_history`].
+    #[cfg(feature = "history")]
+    pub fn borrow_history(&self) -> alloc::vec::Vec<HistoryEntry> {
+        let state = self.state.lock();
+
+        state
+            .history
+            .iter()
+            .flat_map(|history| history.entries().cloned())
+            .collect()
+    }
+
+    /// Returns the counters of borrows taken and failed of this cell, since it was created or since the last
+    /// call to [`Self::reset_borrow_stats`].
+    #[cfg(feature = "stats")]
+    pub fn borrow_stats(&self) -> BorrowStats {
+        self.state.lock().stats.clone()
+    }
+
+    /// Reset the counters returned by [`Self::borrow_stats`] to zero.
+    #[cfg(feature = "stats")]
+    pub fn reset_borrow_stats(&self) {
+        self.state.lock().stats = BorrowStats::new();
+    }
+
+    /// Returns the most recent borrow transitions of this cell as a [`BorrowTrace`].
+    ///
+    /// Replaying the trace against a fresh [`BorrowState`] with this cell's policy reproduces the current
+    /// borrow state, provided history was enabled before the cell was first borrowed and its capacity was
+    /// not exceeded.
+    ///
+    /// Transitions which failed for reasons outside the borrow state, like a full pointer stack or a borrow
+    /// held by another thread, left the state unchanged and are left out of the trace.
+    #[cfg(feature = "history")]
+    pub fn borrow_trace(&self) -> BorrowTrace {
+        let mut replayed = BorrowState::with_policy(self.policy());
+
+        self.borrow_history()
+            .iter()
+            .filter_map(|entry| {
+                let op = BorrowOp::from_event(entry.kind)?;
+                if entry.error.is_some() && op.apply(&mut replayed.clone()).is_ok() {
+                    return None;
+                }
+                let _ = op.apply(&mut replayed);
+
+                Some(op)
+            })
+            .collect()
+    }
+
     pub fn is_currently_bound(self: Pin<&Self>) -> bool {
-        let guard = self.state.lock().unwrap();
+        self.borrow_status().is_bound()
+    }
+}
+
+impl<T: fmt::Debug, const DEPTH: usize, L: LockFamily> fmt::Debug for GdCell<T, DEPTH, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = self.borrow_status();
+        let mut debug = f.debug_struct("GdCell");
+
+        match DebugProbe::new(&self.state) {
+            Some(probe) => {
+                // SAFETY:
+                // The probe holds a shared borrow, therefore there cannot currently be any aliasing mutable
+                // references.
+                debug.field("value", unsafe { self.get_value().as_ref() });
+                drop(probe);
+            }
+            None => {
+                debug.field("value", &format_args!("<mutably bound>"));
+            }
+        }
+
+        debug.field("state", &status).finish()
+    }
+}
+
+/// A shared borrow taken to format a cell, which is not recorded as a transition.
+///
+/// So printing a cell does not show up in hooks, history or stats.
+struct DebugProbe<'a, S: Lock<CellState>> {
+    state: &'a S,
+}
+
+impl<'a, S: Lock<CellState>> DebugProbe<'a, S> {
+    fn new(state: &'a S) -> Option<Self> {
+        let mut guard = state.lock();
+        guard
+            .ensure_may_borrow(BorrowEventKind::Shared)
+            .and_then(|()| guard.borrow.increment_shared())
+            .ok()?;
+
+        Some(Self { state })
+    }
+}
+
+impl<S: Lock<CellState>> Drop for DebugProbe<'_, S> {
+    fn drop(&mut self) {
+        self.state
+            .lock()
+            .borrow
+            .decrement_shared()
+            .expect("the probe holds a shared borrow");
 
-        guard.shared_count() > 0 || guard.mut_count() > 0
+        #[cfg(feature = "std")]
+        blocking::notify_released();
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::pin::pin;
+    use core::pin::pin;
 
     use super::*;
 
@@ -121,7 +616,7 @@ mod test {
 
         assert_eq!(*guard1, VAL);
         assert!(guard2.is_err());
-        std::mem::drop(guard1);
+        core::mem::drop(guard1);
     }
 
     #[test]
@@ -134,7 +629,7 @@ mod test {
 
         assert_eq!(*guard1, VAL);
         assert!(guard2.is_err());
-        std::mem::drop(guard1);
+        core::mem::drop(guard1);
     }
 
     #[test]
@@ -147,7 +642,7 @@ mod test {
 
         assert_eq!(*guard1, VAL);
         assert!(guard2.is_err());
-        std::mem::drop(guard1);
+        core::mem::drop(guard1);
     }
 
     #[test]
@@ -160,7 +655,7 @@ mod test {
 
         assert_eq!(*guard1, VAL);
         assert_eq!(*guard2, VAL);
-        std::mem::drop(guard1);
+        core::mem::drop(guard1);
     }
 
     #[test]
@@ -242,4 +737,289 @@ mod test {
         drop(guard1);
         drop(guard2);
     }
+
+    #[test]
+    fn prevent_reentrancy_past_depth() {
+        const VAL: i32 = 7;
+        let cell = pin!(GdCell::<i32, 1>::new_with_depth(VAL));
+        let cell = cell.into_ref();
+
+        let mut guard1 = cell.gd_mut().unwrap();
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard1).unwrap();
+
+        let mut guard2 = cell.gd_mut().unwrap();
+        let err = cell
+            .set_non_aliasing(&mut *guard2)
+            .expect_err("should not allow more than `DEPTH` non-aliasing borrows");
+        assert_eq!(
+            err.downcast_ref::<BorrowErr>().map(|err| &err.error),
+            Some(&BorrowStateErr::ReentrancyLimit {
+                depth: 1,
+                cell: Some(cell.address())
+            })
+        );
+
+        *guard2 += 1;
+        drop(guard2);
+        drop(no_alias_guard);
+
+        assert_eq!(*guard1, VAL + 1);
+    }
+
+    #[test]
+    fn upgradable_allows_shared() {
+        const VAL: i32 = 321;
+        let cell = pin!(GdCell::new(VAL));
+        let cell = cell.into_ref();
+
+        let upgradable = cell.gd_upgradable().unwrap();
+        let shared = cell.gd_ref().unwrap();
+
+        assert_eq!(*upgradable, VAL);
+        assert_eq!(*shared, VAL);
+        cell.gd_upgradable()
+            .expect_err("should not allow two upgradable borrows");
+        cell.gd_mut()
+            .expect_err("should not allow mutable borrow while upgradable borrow exists");
+
+        let (upgradable, err) = upgradable
+            .upgrade()
+            .map(|_| ())
+            .expect_err("should not upgrade while shared borrow exists");
+        assert_eq!(err.kind, BorrowEventKind::Upgrade);
+        assert_eq!(err.error, BorrowStateErr::HasSharedRef);
+        assert_eq!(err.status.shared_count, 2);
+        drop(shared);
+
+        let mut guard = upgradable.upgrade().unwrap();
+        *guard += 1;
+        cell.gd_ref()
+            .expect_err("should not allow shared borrow while upgraded");
+        drop(guard);
+
+        assert_eq!(*cell.gd_ref().unwrap(), VAL + 1);
+        assert!(!cell.is_currently_bound());
+    }
+
+    #[test]
+    fn upgrade_non_aliasing() {
+        const VAL: i32 = 42;
+        let cell = pin!(GdCell::new(VAL));
+        let cell = cell.into_ref();
+
+        let mut guard1 = cell.gd_mut().unwrap();
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard1).unwrap();
+
+        let mut guard2 = cell.gd_upgradable().unwrap().upgrade().unwrap();
+        *guard2 += 1;
+        drop(guard2);
+        drop(no_alias_guard);
+
+        assert_eq!(*guard1, VAL + 1);
+    }
+
+    #[test]
+    fn readonly_non_aliasing_allows_only_shared() {
+        const VAL: i32 = 1000;
+        let cell = pin!(GdCell::new(VAL));
+        let cell = cell.into_ref();
+
+        let mut guard1 = cell.gd_mut().unwrap();
+        *guard1 += 1;
+        let no_alias_guard = cell.set_non_aliasing_readonly(&mut *guard1).unwrap();
+
+        let guard2 = cell.gd_ref().unwrap();
+        assert_eq!(*guard2, VAL + 1);
+
+        let err = cell
+            .gd_mut()
+            .expect_err("should not allow mutable reentrancy when read-only");
+        assert_eq!(
+            err.downcast_ref::<BorrowErr>().map(|err| &err.error),
+            Some(&BorrowStateErr::IsReadOnly)
+        );
+        drop(guard2);
+        drop(no_alias_guard);
+
+        *guard1 += 1;
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard1).unwrap();
+        *cell.gd_mut().unwrap() += 1;
+        drop(no_alias_guard);
+
+        assert_eq!(*guard1, VAL + 3);
+    }
+
+    #[test]
+    fn policy_forbid() {
+        let cell = pin!(GdCell::with_policy(0, ReentrancyPolicy::Forbid));
+        let cell = cell.into_ref();
+
+        let mut guard = cell.gd_mut().unwrap();
+        let err = cell
+            .set_non_aliasing(&mut *guard)
+            .expect_err("policy should forbid reentrancy");
+        assert_eq!(
+            err.downcast_ref::<BorrowErr>().map(|err| &err.error),
+            Some(&BorrowStateErr::PolicyViolation(ReentrancyPolicy::Forbid))
+        );
+    }
+
+    #[test]
+    fn policy_shared_only() {
+        let cell = pin!(GdCell::with_policy(0, ReentrancyPolicy::SharedOnly));
+        let cell = cell.into_ref();
+
+        let mut guard = cell.gd_mut().unwrap();
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard).unwrap();
+
+        assert_eq!(*cell.gd_ref().unwrap(), 0);
+        let err = cell
+            .gd_mut()
+            .expect_err("policy should forbid mutable reentrancy");
+        assert_eq!(
+            err.downcast_ref::<BorrowErr>().map(|err| &err.error),
+            Some(&BorrowStateErr::PolicyViolation(
+                ReentrancyPolicy::SharedOnly
+            ))
+        );
+        drop(no_alias_guard);
+    }
+
+    #[test]
+    fn policy_mutable_max_depth() {
+        let policy = ReentrancyPolicy::Mutable { max_depth: 1 };
+        let cell = pin!(GdCell::with_policy(0, policy));
+        let cell = cell.into_ref();
+
+        let mut guard1 = cell.gd_mut().unwrap();
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard1).unwrap();
+
+        let mut guard2 = cell.gd_mut().unwrap();
+        let err = cell
+            .set_non_aliasing(&mut *guard2)
+            .expect_err("policy should forbid reentrancy past `max_depth`");
+        assert_eq!(
+            err.downcast_ref::<BorrowErr>().map(|err| &err.error),
+            Some(&BorrowStateErr::ReentrancyLimit {
+                depth: 1,
+                cell: Some(cell.address())
+            })
+        );
+        drop(guard2);
+        drop(no_alias_guard);
+    }
+
+    #[test]
+    fn max_depth_is_clamped_to_supported_depth() {
+        let cell = GdCell::with_max_depth(0, DEFAULT_REENTRANCY_DEPTH + 1);
+        assert_eq!(
+            cell.policy(),
+            ReentrancyPolicy::Mutable {
+                max_depth: DEFAULT_REENTRANCY_DEPTH
+            }
+        );
+    }
+
+    #[test]
+    fn max_depth_stops_runaway_recursion() {
+        fn recurse(cell: Pin<&GdCell<i32>>, depth: &mut usize) -> Result<(), Box<dyn Error>> {
+            let mut guard = cell.gd_mut()?;
+            *guard += 1;
+            *depth += 1;
+            let _no_alias_guard = cell.set_non_aliasing(&mut *guard)?;
+            recurse(cell, depth)
+        }
+
+        let cell = pin!(GdCell::with_max_depth(0, 3));
+        let cell = cell.into_ref();
+
+        let mut depth = 0;
+        let err = recurse(cell, &mut depth).expect_err("recursion should be stopped");
+        assert_eq!(
+            err.downcast_ref::<BorrowErr>().map(|err| &err.error),
+            Some(&BorrowStateErr::ReentrancyLimit {
+                depth: 3,
+                cell: Some(cell.address())
+            })
+        );
+        assert_eq!(depth, 4);
+        assert_eq!(*cell.gd_ref().unwrap(), 4);
+
+        cell.set_policy(ReentrancyPolicy::default());
+        depth = 0;
+        let err = recurse(cell, &mut depth).expect_err("recursion should be stopped");
+        assert_eq!(
+            err.downcast_ref::<BorrowErr>().map(|err| &err.error),
+            Some(&BorrowStateErr::ReentrancyLimit {
+                depth: DEFAULT_REENTRANCY_DEPTH,
+                cell: Some(cell.address())
+            })
+        );
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn debug_does_not_record() {
+        let cell = pin!(GdCell::new(0));
+        let cell = cell.into_ref();
+        let stats = cell.borrow_stats();
+
+        let debug = alloc::format!("{:?}", cell);
+        assert!(debug.starts_with("GdCell { value: 0"), "{debug}");
+        assert_eq!(cell.borrow_stats(), stats);
+
+        let guard = cell.gd_mut().unwrap();
+        let stats = cell.borrow_stats();
+        let debug = alloc::format!("{:?}", cell);
+        assert!(debug.contains("<mutably bound>"), "{debug}");
+        assert_eq!(cell.borrow_stats(), stats);
+        drop(guard);
+    }
+
+    #[test]
+    fn borrow_status_snapshot() {
+        let cell = pin!(GdCell::new(0));
+        let cell = cell.into_ref();
+        assert!(!cell.borrow_status().is_bound());
+
+        let mut guard1 = cell.gd_mut().unwrap();
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard1).unwrap();
+        let guard2 = cell.gd_ref().unwrap();
+
+        let status = cell.borrow_status();
+        assert_eq!(status.shared_count, 1);
+        assert_eq!(status.mut_count, 1);
+        assert_eq!(status.non_aliasing_count, 1);
+        assert_eq!(status.reentrancy_depth, 1);
+        assert!(!status.poisoned);
+
+        let debug = alloc::format!("{:?}", cell);
+        assert!(debug.starts_with("GdCell { value: 0, state: BorrowStatus { shared_count: 1"));
+
+        drop(guard2);
+        drop(no_alias_guard);
+        drop(guard1);
+        assert!(!cell.borrow_status().is_bound());
+    }
+
+    #[test]
+    fn debug_shows_value_unless_mutably_bound() {
+        let cell = pin!(GdCell::new(5));
+        let cell = cell.into_ref();
+
+        assert!(alloc::format!("{:?}", cell).starts_with("GdCell { value: 5, state: "));
+
+        let mut guard = cell.gd_mut().unwrap();
+        assert_eq!(alloc::format!("{:?} {}", guard, guard), "5 5");
+        assert!(
+            alloc::format!("{:?}", cell).starts_with("GdCell { value: <mutably bound>, state: ")
+        );
+
+        *guard += 1;
+        drop(guard);
+
+        let guard = cell.gd_ref().unwrap();
+        assert_eq!(alloc::format!("{:?}", guard), "6");
+        assert!(alloc::format!("{:?}", cell).starts_with("GdCell { value: 6, state: "));
+    }
 }
@@ -0,0 +1,267 @@
+//! Lock primitives used to protect the internal state of a [`GdCell`](crate::GdCell).
+//!
+//! A cell is protected by the locks of a [`LockFamily`], its last type parameter. By default this is
+//! [`DefaultLockFamily`]: with the `std` feature enabled (the default) its locks are `StdMutex`es, and
+//! without it they are [`SpinLock`]s, which only rely on `core`. Platforms with their own mutex can implement
+//! [`Lock`] and [`LockFamily`] for it.
+
+use core::{
+    cell::UnsafeCell,
+    fmt, hint,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A mutual exclusion primitive protecting a value of type `T`.
+pub trait Lock<T> {
+    /// The guard returned by [`Lock::lock`], the lock is released when it is dropped.
+    type Guard<'a>: DerefMut<Target = T>
+    where
+        Self: 'a;
+
+    /// Create a new unlocked lock containing `value`.
+    fn new(value: T) -> Self;
+
+    /// Acquire the lock, blocking the current thread until it is able to do so.
+    fn lock(&self) -> Self::Guard<'_>;
+}
+
+/// A kind of lock, which can protect a value of any type. A [`GdCell`](crate::GdCell) uses it to lock its
+/// internal state.
+///
+/// This is usually implemented for an uninhabited marker type, like [`SpinLockFamily`].
+///
+/// # Safety
+///
+/// The locks must be mutually exclusive across threads, so that they may be sent to and shared between threads
+/// whenever the protected value may be sent to another thread.
+pub unsafe trait LockFamily: 'static {
+    /// The lock protecting a value of type `T`.
+    type Lock<T>: Lock<T>;
+}
+
+/// The family of [`SpinLock`]s.
+#[derive(Debug)]
+pub enum SpinLockFamily {}
+
+// SAFETY:
+// A spin-lock is shared through an atomic flag, so it excludes other threads as well.
+unsafe impl LockFamily for SpinLockFamily {
+    type Lock<T> = SpinLock<T>;
+}
+
+/// The family of [`StdMutex`]es.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum StdMutexFamily {}
+
+// SAFETY:
+// A std mutex excludes other threads.
+#[cfg(feature = "std")]
+unsafe impl LockFamily for StdMutexFamily {
+    type Lock<T> = StdMutex<T>;
+}
+
+/// The locks used by [`GdCell`](crate::GdCell) unless another [`LockFamily`] is chosen.
+#[cfg(feature = "std")]
+pub type DefaultLockFamily = StdMutexFamily;
+
+/// The locks used by [`GdCell`](crate::GdCell) unless another [`LockFamily`] is chosen.
+#[cfg(not(feature = "std"))]
+pub type DefaultLockFamily = SpinLockFamily;
+
+/// The lock of [`DefaultLockFamily`], which can also be created in a `static`.
+#[cfg(feature = "std")]
+pub type DefaultLock<T> = StdMutex<T>;
+
+/// The lock of [`DefaultLockFamily`], which can also be created in a `static`.
+#[cfg(not(feature = "std"))]
+pub type DefaultLock<T> = SpinLock<T>;
+
+/// A lock which busy-waits until it can be acquired.
+///
+/// This only depends on `core`, and is therefore available without the `std` feature.
+pub struct SpinLock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY:
+// The lock ensures only one thread can access the value at a time, so it is enough for `T` to be `Send`.
+unsafe impl<T: Send> Send for SpinLock<T> {}
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    /// Create a new unlocked spin-lock containing `value`.
+    pub const fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+}
+
+impl<T> Lock<T> for SpinLock<T> {
+    type Guard<'a>
+        = SpinLockGuard<'a, T>
+    where
+        T: 'a;
+
+    fn new(value: T) -> Self {
+        Self::new(value)
+    }
+
+    fn lock(&self) -> Self::Guard<'_> {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+
+        SpinLockGuard { lock: self }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SpinLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpinLock")
+            .field("value", &*self.lock())
+            .finish()
+    }
+}
+
+/// The guard of a locked [`SpinLock`].
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<'a, T> Deref for SpinLockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // This guard holds the lock, so there are no other references to the value.
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<'a, T> DerefMut for SpinLockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // This guard holds the lock, so there are no other references to the value.
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<'a, T> Drop for SpinLockGuard<'a, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+/// A [`std::sync::Mutex`] used as a [`Lock`].
+///
+/// Panics on lock if the mutex has been poisoned.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StdMutex<T>(std::sync::Mutex<T>);
+
+#[cfg(feature = "std")]
+impl<T> StdMutex<T> {
+    /// Create a new unlocked mutex containing `value`.
+    pub const fn new(value: T) -> Self {
+        Self(std::sync::Mutex::new(value))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Lock<T> for StdMutex<T> {
+    type Guard<'a>
+        = std::sync::MutexGuard<'a, T>
+    where
+        T: 'a;
+
+    fn new(value: T) -> Self {
+        Self::new(value)
+    }
+
+    fn lock(&self) -> Self::Guard<'_> {
+        self.0.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::{pin::pin, sync::atomic::AtomicUsize};
+
+    use super::*;
+    use crate::{GdCell, ReentrancyPolicy, DEFAULT_REENTRANCY_DEPTH};
+
+    #[test]
+    fn spin_lock_excludes() {
+        let lock = SpinLock::new(5);
+        let mut guard = lock.lock();
+        *guard += 1;
+        assert!(lock.locked.load(Ordering::Relaxed));
+        drop(guard);
+
+        assert!(!lock.locked.load(Ordering::Relaxed));
+        assert_eq!(*lock.lock(), 6);
+    }
+
+    /// A lock counting how often it was acquired, standing in for a platform's own mutex.
+    struct CountingLock<T> {
+        lock: SpinLock<T>,
+        count: AtomicUsize,
+    }
+
+    impl<T> Lock<T> for CountingLock<T> {
+        type Guard<'a>
+            = SpinLockGuard<'a, T>
+        where
+            T: 'a;
+
+        fn new(value: T) -> Self {
+            Self {
+                lock: SpinLock::new(value),
+                count: AtomicUsize::new(0),
+            }
+        }
+
+        fn lock(&self) -> Self::Guard<'_> {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            self.lock.lock()
+        }
+    }
+
+    enum CountingLockFamily {}
+
+    // SAFETY:
+    // The counted spin-lock excludes other threads like `SpinLock` does.
+    unsafe impl LockFamily for CountingLockFamily {
+        type Lock<T> = CountingLock<T>;
+    }
+
+    #[test]
+    fn cell_uses_chosen_lock() {
+        let cell = pin!(
+            GdCell::<_, DEFAULT_REENTRANCY_DEPTH, CountingLockFamily>::new_with_lock(
+                1,
+                ReentrancyPolicy::default()
+            )
+        );
+        let cell = cell.into_ref();
+
+        let mut guard = cell.gd_mut().unwrap();
+        *guard += 1;
+        assert!(cell.gd_ref().is_err());
+        drop(guard);
+
+        assert_eq!(*cell.gd_ref().unwrap(), 2);
+        assert!(cell.state.count.load(Ordering::Relaxed) > 0);
+    }
+}
@@ -0,0 +1,244 @@
+//! Taking mutable borrows of several [`GdCell`]s at once, either all of them or none.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{error::Error, pin::Pin};
+
+use crate::borrow_err::Failure;
+use crate::borrow_state::BorrowStateErr;
+use crate::cell_state::CellState;
+use crate::guards::GdMut;
+use crate::hooks::{BorrowEventKind, EventSource};
+use crate::lock::{Lock, LockFamily};
+use crate::status::BorrowStatus;
+use crate::GdCell;
+
+/// A cell to take a mutable borrow of, with its type erased.
+struct Target<'a, L: LockFamily> {
+    address: usize,
+    state: &'a L::Lock<CellState>,
+    source: EventSource,
+}
+
+impl<'a, L: LockFamily> Target<'a, L> {
+    fn new<T, const DEPTH: usize>(cell: &'a GdCell<T, DEPTH, L>, source: EventSource) -> Self {
+        Self {
+            address: cell.address(),
+            state: &cell.state,
+            source,
+        }
+    }
+}
+
+/// Take a mutable borrow of every target, or of none of them.
+///
+/// Returns the mutable borrow count of each target, or the index of the target which could not be borrowed.
+fn acquire<L: LockFamily>(targets: &[Target<'_, L>]) -> Result<Vec<usize>, (usize, Failure)> {
+    let mut order = (0..targets.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| targets[i].address);
+
+    if let Some(pair) = order
+        .windows(2)
+        .find(|pair| targets[pair[0]].address == targets[pair[1]].address)
+    {
+        let failure = Failure {
+            error: BorrowStateErr::DuplicateCell,
+            status: BorrowStatus::new(&targets[pair[1]].state.lock().borrow),
+        };
+        return Err((pair[1], failure));
+    }
+
+    // Locking in address order means concurrent calls with overlapping cells cannot deadlock each other.
+    let mut states = order
+        .iter()
+        .map(|&i| targets[i].state.lock())
+        .collect::<Vec<_>>();
+
+    // Try every borrow on a copy first, so nothing needs to be undone if one of them fails.
+    let failure = states.iter().enumerate().find_map(|(position, state)| {
+        state
+            .ensure_may_borrow(BorrowEventKind::Mut)
+            .and_then(|()| state.borrow.clone().increment_mut())
+            .err()
+            .map(|err| (position, err.in_cell(targets[order[position]].address)))
+    });
+
+    let mut events = Vec::new();
+    let result = match failure {
+        Some((position, mut err)) => {
+            let target = &targets[order[position]];
+            events.push(target.source.record(
+                BorrowEventKind::Mut,
+                &mut states[position],
+                Some(&mut err),
+            ));
+
+            let status = BorrowStatus::new(&states[position].borrow);
+            Err((order[position], Failure { error: err, status }))
+        }
+        None => {
+            let mut counts = vec![0; targets.len()];
+
+            for (&i, state) in order.iter().zip(&mut states) {
+                let mut result = state.borrow.increment_mut();
+                events.push(targets[i].source.record(
+                    BorrowEventKind::Mut,
+                    state,
+                    result.as_mut().err(),
+                ));
+                counts[i] = result.expect("the same borrow succeeded on a copy of the state");
+            }
+
+            Ok(counts)
+        }
+    };
+
+    drop(states);
+    for event in events {
+        event.emit();
+    }
+
+    result
+}
+
+/// Take a mutable borrow of every cell in `cells`, or of none of them.
+///
+/// Fails with [`BorrowStateErr::DuplicateCell`] if the same cell is passed more than once, or with the error
+/// of the first cell which cannot be borrowed mutably. The cells are locked in order of their address, so
+/// concurrent calls cannot deadlock regardless of the order the cells are passed in.
+#[track_caller]
+pub fn gd_mut_many<'a, T, const DEPTH: usize, L: LockFamily, const N: usize>(
+    cells: [Pin<&'a GdCell<T, DEPTH, L>>; N],
+) -> Result<[GdMut<'a, T, L>; N], Box<dyn Error>> {
+    let mut targets = Vec::with_capacity(N);
+    for cell in &cells {
+        targets.push(Target::new(cell.get_ref(), cell.event_source()));
+    }
+
+    let counts = acquire(&targets).map_err(|(i, err)| {
+        cells[i]
+            .check(BorrowEventKind::Mut, Err::<(), _>(err))
+            .unwrap_err()
+    })?;
+
+    // SAFETY:
+    // `increment_mut` succeeded for every cell, so each guard is sound for the same reasons as in
+    // `GdCell::gd_mut`. No cell is borrowed twice, since duplicates were rejected.
+    Ok(core::array::from_fn(|i| unsafe {
+        GdMut::new(
+            &cells[i].get_ref().state,
+            counts[i],
+            cells[i].get_value(),
+            targets[i].source,
+        )
+    }))
+}
+
+/// Take a mutable borrow of both `a` and `b`, or of neither.
+///
+/// This behaves like [`gd_mut_many`], but the cells may store different types. Both use the same locks.
+#[track_caller]
+#[allow(clippy::type_complexity)]
+pub fn gd_mut_pair<'a, A, B, const DEPTH_A: usize, const DEPTH_B: usize, L: LockFamily>(
+    a: Pin<&'a GdCell<A, DEPTH_A, L>>,
+    b: Pin<&'a GdCell<B, DEPTH_B, L>>,
+) -> Result<(GdMut<'a, A, L>, GdMut<'a, B, L>), Box<dyn Error>> {
+    let targets = [
+        Target::new(a.get_ref(), a.event_source()),
+        Target::new(b.get_ref(), b.event_source()),
+    ];
+
+    let counts = acquire(&targets).map_err(|(i, err)| {
+        let result = Err::<(), _>(err);
+        match i {
+            0 => a.check(BorrowEventKind::Mut, result).unwrap_err(),
+            _ => b.check(BorrowEventKind::Mut, result).unwrap_err(),
+        }
+    })?;
+
+    // SAFETY:
+    // See `gd_mut_many`.
+    unsafe {
+        Ok((
+            GdMut::new(
+                &a.get_ref().state,
+                counts[0],
+                a.get_value(),
+                targets[0].source,
+            ),
+            GdMut::new(
+                &b.get_ref().state,
+                counts[1],
+                b.get_value(),
+                targets[1].source,
+            ),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::pin::pin;
+
+    use super::*;
+    use crate::BorrowErr;
+
+    #[test]
+    fn borrows_all_or_none() {
+        let a = pin!(GdCell::new(1));
+        let a = a.into_ref();
+        let b = pin!(GdCell::new(2));
+        let b = b.into_ref();
+        let c = pin!(GdCell::new(3));
+        let c = c.into_ref();
+
+        let [mut guard_c, mut guard_a] = gd_mut_many([c, a]).unwrap();
+        *guard_c += *guard_a;
+        *guard_a = 0;
+        drop(guard_a);
+        drop(guard_c);
+        assert_eq!(*c.gd_ref().unwrap(), 4);
+
+        let shared_b = b.gd_ref().unwrap();
+        let err = gd_mut_many([a, b, c]).map(|_| ()).unwrap_err();
+        let err = err.downcast_ref::<BorrowErr>().unwrap();
+        assert_eq!(err.error, BorrowStateErr::HasSharedRef);
+        assert_eq!(err.status.shared_count, 1);
+        drop(shared_b);
+
+        assert!(!a.borrow_status().is_bound());
+        assert!(!c.borrow_status().is_bound());
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let a = pin!(GdCell::new(1));
+        let a = a.into_ref();
+        let b = pin!(GdCell::new(2));
+        let b = b.into_ref();
+
+        let err = gd_mut_many([a, b, a]).map(|_| ()).unwrap_err();
+        let err = err.downcast_ref::<BorrowErr>().unwrap();
+        assert_eq!(err.error, BorrowStateErr::DuplicateCell);
+
+        assert!(gd_mut_pair(b, b).is_err());
+        assert!(!a.borrow_status().is_bound());
+        assert!(!b.borrow_status().is_bound());
+    }
+
+    #[test]
+    fn pair_of_different_types() {
+        let items = pin!(GdCell::new(alloc::vec![1, 2, 3]));
+        let items = items.into_ref();
+        let count = pin!(GdCell::new(0usize));
+        let count = count.into_ref();
+
+        let (mut items_guard, mut count_guard) = gd_mut_pair(items, count).unwrap();
+        *count_guard += items_guard.len();
+        items_guard.clear();
+        assert!(gd_mut_pair(items, count).is_err());
+        drop(items_guard);
+        drop(count_guard);
+
+        assert_eq!(*count.gd_ref().unwrap(), 3);
+    }
+}
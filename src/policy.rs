@@ -0,0 +1,39 @@
+use core::fmt;
+
+/// Which kinds of reentrant borrows a [`GdCell`](crate::GdCell) allows.
+///
+/// A reentrant borrow is one taken while a mutable borrow is marked as non-aliasing, see
+/// [`GdCell::set_non_aliasing`](crate::GdCell::set_non_aliasing).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReentrancyPolicy {
+    /// No reentrant borrows are allowed, marking a mutable borrow as non-aliasing always fails.
+    Forbid,
+    /// Only shared reentrant borrows are allowed, every non-aliasing borrow is read-only.
+    SharedOnly,
+    /// Shared and mutable reentrant borrows are allowed, with at most `max_depth` nested non-aliasing
+    /// borrows.
+    ///
+    /// Going past this fails with [`BorrowStateErr::ReentrancyLimit`](crate::BorrowStateErr).
+    Mutable { max_depth: usize },
+}
+
+impl Default for ReentrancyPolicy {
+    fn default() -> Self {
+        Self::Mutable {
+            max_depth: usize::MAX,
+        }
+    }
+}
+
+impl fmt::Display for ReentrancyPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Forbid => write!(f, "`Forbid` does not allow reentrant borrows"),
+            Self::SharedOnly => write!(f, "`SharedOnly` does not allow mutable reentrant borrows"),
+            Self::Mutable { max_depth } => write!(
+                f,
+                "`Mutable` does not allow more than {max_depth} nested non-aliasing borrows"
+            ),
+        }
+    }
+}
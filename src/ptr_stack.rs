@@ -0,0 +1,57 @@
+use core::ptr::NonNull;
+
+/// A fixed-capacity stack of the pointers handed to [`GdCell::set_non_aliasing`](crate::GdCell::set_non_aliasing).
+///
+/// The pointers are stored inline, so pushing never allocates. Callers check [`PtrStack::is_full`] before
+/// pushing, and report a full stack as [`BorrowStateErr::ReentrancyLimit`](crate::BorrowStateErr::ReentrancyLimit).
+#[derive(Debug)]
+pub struct PtrStack<T, const DEPTH: usize> {
+    ptrs: [Option<NonNull<T>>; DEPTH],
+    len: usize,
+}
+
+impl<T, const DEPTH: usize> PtrStack<T, DEPTH> {
+    /// Create a new empty pointer stack.
+    pub fn new() -> Self {
+        Self {
+            ptrs: [None; DEPTH],
+            len: 0,
+        }
+    }
+
+    /// Returns the most recently pushed pointer, if any.
+    pub fn last(&self) -> Option<NonNull<T>> {
+        self.len.checked_sub(1).and_then(|i| self.ptrs[i])
+    }
+
+    /// Returns the number of pointers on the stack.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no more pointers can be pushed.
+    pub fn is_full(&self) -> bool {
+        self.len == DEPTH
+    }
+
+    /// Push a new pointer onto the stack.
+    ///
+    /// # Panics
+    ///
+    /// If there already are `DEPTH` pointers on the stack.
+    pub fn push(&mut self, ptr: NonNull<T>) {
+        assert!(!self.is_full(), "pushed more than {DEPTH} pointers");
+
+        self.ptrs[self.len] = Some(ptr);
+        self.len += 1;
+    }
+
+    /// Remove the most recently pushed pointer from the stack and return it.
+    pub fn pop(&mut self) -> Option<NonNull<T>> {
+        let ptr = self.last()?;
+        self.len -= 1;
+        self.ptrs[self.len] = None;
+
+        Some(ptr)
+    }
+}
@@ -0,0 +1,395 @@
+//! A process-wide registry of instances stored in [`GdCell`]s, addressed by [`InstanceId`].
+//!
+//! Only available with the `std` feature, since instances are shared between threads. The same goes for
+//! everything built on the registry: [`Base`], and calling methods by id or by name. Ids are never reused, so
+//! an id or a [`WeakInstance`] held after its instance was freed reliably refers to nothing.
+//!
+//! Instances are either freed manually, see [`register_instance`], or reference-counted, see
+//! [`register_ref_counted`].
+
+use alloc::{collections::BTreeMap, sync::Arc};
+use core::{
+    any::{Any, TypeId},
+    fmt,
+    marker::PhantomData,
+    ops::Deref,
+    pin::Pin,
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use thiserror::Error;
+
+use crate::base::Base;
+use crate::handle::GdHandle;
+use crate::lock::{DefaultLock, Lock};
+use crate::GdCell;
+
+/// The id of an instance in the registry, returned by [`register_instance`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InstanceId(u64);
+
+impl InstanceId {
+    /// Returns a weak handle to the instance with this id, which stores a `T`.
+    pub fn downgrade<T>(self) -> WeakInstance<T> {
+        WeakInstance {
+            id: self,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl fmt::Display for InstanceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+struct Entry {
+    /// A `GdCell<T>` for some `T`, which is never moved out of its `Arc`. So it may be treated as pinned.
+    cell: Arc<dyn Any + Send + Sync>,
+    /// The number of live [`Instance`]s, or `None` if the instance is freed manually.
+    strong: Option<usize>,
+}
+
+/// The entries of all live instances.
+static INSTANCES: DefaultLock<BTreeMap<InstanceId, Entry>> = DefaultLock::new(BTreeMap::new());
+
+fn insert<T: Send + Sync + 'static>(
+    init: impl FnOnce(Base<T>) -> T,
+    strong: Option<usize>,
+) -> (InstanceId, Arc<GdCell<T>>) {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+    let id = InstanceId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let cell = Arc::new(GdCell::new(init(Base::new(id))));
+    INSTANCES.lock().insert(
+        id,
+        Entry {
+            cell: cell.clone(),
+            strong,
+        },
+    );
+
+    (id, cell)
+}
+
+/// Move `value` into a new cell owned by the registry, and return the id of the new instance.
+///
+/// The instance stays alive until it is freed with [`free_instance`].
+pub fn register_instance<T: Send + Sync + 'static>(value: T) -> InstanceId {
+    register_instance_with(|_| value)
+}
+
+/// Register the value returned by `init` like [`register_instance`], passing `init` the [`Base`] of the new
+/// instance to store in the value.
+pub fn register_instance_with<T: Send + Sync + 'static>(
+    init: impl FnOnce(Base<T>) -> T,
+) -> InstanceId {
+    insert(init, None).0
+}
+
+/// Move `value` into a new reference-counted cell in the registry, and return the only strong handle to it.
+///
+/// The instance is freed when the last [`Instance`] referring to it is dropped.
+pub fn register_ref_counted<T: Send + Sync + 'static>(value: T) -> Instance<T> {
+    register_ref_counted_with(|_| value)
+}
+
+/// Register the value returned by `init` like [`register_ref_counted`], passing `init` the [`Base`] of the
+/// new instance to store in the value.
+pub fn register_ref_counted_with<T: Send + Sync + 'static>(
+    init: impl FnOnce(Base<T>) -> T,
+) -> Instance<T> {
+    let (id, cell) = insert(init, Some(1));
+
+    Instance::new(id, cell)
+}
+
+/// Returns a strong handle to the instance with id `id`, or `None` if it was freed or does not store a `T`.
+pub fn instance<T: Send + Sync + 'static>(id: InstanceId) -> Option<Instance<T>> {
+    try_instance(id).ok()
+}
+
+/// Returns a strong handle to the instance with id `id`, or why there is none.
+pub fn try_instance<T: Send + Sync + 'static>(id: InstanceId) -> Result<Instance<T>, InstanceErr> {
+    let mut instances = INSTANCES.lock();
+    let entry = instances.get_mut(&id).ok_or(InstanceErr::Freed(id))?;
+    let cell = entry
+        .cell
+        .clone()
+        .downcast::<GdCell<T>>()
+        .map_err(|_| InstanceErr::WrongType {
+            id,
+            expected: core::any::type_name::<T>(),
+        })?;
+
+    // Counted while the registry is locked, so the instance cannot be freed in between.
+    if let Some(strong) = &mut entry.strong {
+        *strong += 1;
+    }
+    drop(instances);
+
+    Ok(Instance::new(id, cell))
+}
+
+/// Returns the type id of the cell storing the instance with id `id`.
+pub(crate) fn instance_type_id(id: InstanceId) -> Result<TypeId, InstanceErr> {
+    let instances = INSTANCES.lock();
+    let entry = instances.get(&id).ok_or(InstanceErr::Freed(id))?;
+
+    Ok((*entry.cell).type_id())
+}
+
+/// Returns `true` if the instance with id `id` has not been freed.
+pub fn is_instance_alive(id: InstanceId) -> bool {
+    INSTANCES.lock().contains_key(&id)
+}
+
+/// Remove the instance with id `id` from the registry, returning `false` if it was already freed.
+///
+/// This also frees reference-counted instances, regardless of how many [`Instance`]s refer to them. Its value
+/// is dropped once the last handle to it is dropped as well.
+pub fn free_instance(id: InstanceId) -> bool {
+    let entry = INSTANCES.lock().remove(&id);
+
+    // Dropped after the registry is unlocked, since dropping the value may free other instances.
+    entry.is_some()
+}
+
+/// Error returned when looking up an instance in the registry fails.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InstanceErr {
+    #[error("instance {0} was freed")]
+    Freed(InstanceId),
+    #[error("instance {id} does not store a `{expected}`")]
+    WrongType {
+        id: InstanceId,
+        expected: &'static str,
+    },
+}
+
+/// A strong handle to an instance in the registry, which dereferences to a [`GdHandle`] of its cell.
+///
+/// Cloning and dropping the handle counts the references to a reference-counted instance, and the last one
+/// frees it. If the cell is still bound at that point, through a [`GdHandle`] cloned from this one, the value
+/// is only dropped once that handle is dropped. Handles to manually freed instances are not counted.
+pub struct Instance<T> {
+    id: InstanceId,
+    handle: GdHandle<T>,
+}
+
+impl<T> Instance<T> {
+    fn new(id: InstanceId, cell: Arc<GdCell<T>>) -> Self {
+        // SAFETY:
+        // Cells in the registry are never moved out of their `Arc`.
+        let cell = unsafe { Pin::new_unchecked(cell) };
+
+        Self {
+            id,
+            handle: cell.into(),
+        }
+    }
+
+    /// Returns the id of the instance.
+    pub fn id(&self) -> InstanceId {
+        self.id
+    }
+
+    /// Returns a weak handle to the instance.
+    pub fn downgrade(&self) -> WeakInstance<T> {
+        self.id.downgrade()
+    }
+
+    /// Returns the number of strong handles to the instance, or `None` if it is freed manually or was
+    /// already freed.
+    pub fn strong_count(&self) -> Option<usize> {
+        INSTANCES.lock().get(&self.id)?.strong
+    }
+}
+
+impl<T> Deref for Instance<T> {
+    type Target = GdHandle<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handle
+    }
+}
+
+impl<T> Clone for Instance<T> {
+    fn clone(&self) -> Self {
+        if let Some(Entry {
+            strong: Some(strong),
+            ..
+        }) = INSTANCES.lock().get_mut(&self.id)
+        {
+            *strong += 1;
+        }
+
+        Self {
+            id: self.id,
+            handle: self.handle.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Instance<T> {
+    fn drop(&mut self) {
+        let mut instances = INSTANCES.lock();
+        let Some(Entry {
+            strong: Some(strong),
+            ..
+        }) = instances.get_mut(&self.id)
+        else {
+            return;
+        };
+
+        *strong -= 1;
+        if *strong == 0 {
+            let entry = instances.remove(&self.id);
+
+            // Dropped after the registry is unlocked, see `free_instance`.
+            drop(instances);
+            drop(entry);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Instance<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Instance")
+            .field("id", &self.id)
+            .field("cell", &self.handle)
+            .finish()
+    }
+}
+
+/// A handle to an instance in the registry which does not keep the instance alive.
+///
+/// This can be held by signal connections or caches which may outlive the instance. Use
+/// [`WeakInstance::upgrade`] to access the instance only while it is alive.
+pub struct WeakInstance<T> {
+    id: InstanceId,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> WeakInstance<T> {
+    /// Returns the id of the instance.
+    pub fn id(&self) -> InstanceId {
+        self.id
+    }
+}
+
+impl<T: Send + Sync + 'static> WeakInstance<T> {
+    /// Returns a strong handle to the instance, or `None` if it was freed.
+    pub fn upgrade(&self) -> Option<Instance<T>> {
+        instance(self.id)
+    }
+
+    /// Returns `true` if the instance has not been freed.
+    pub fn is_alive(&self) -> bool {
+        is_instance_alive(self.id)
+    }
+}
+
+impl<T> Clone for WeakInstance<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for WeakInstance<T> {}
+
+impl<T> fmt::Debug for WeakInstance<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("WeakInstance").field(&self.id).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+
+    #[test]
+    fn weak_instance_upgrades_while_alive() {
+        let id = register_instance(5);
+        let weak = id.downgrade::<i32>();
+
+        let handle = weak.upgrade().unwrap();
+        assert_eq!(*handle.bind().unwrap(), 5);
+        assert!(matches!(
+            try_instance::<u8>(id),
+            Err(InstanceErr::WrongType { .. })
+        ));
+
+        assert!(free_instance(id));
+        assert!(!free_instance(id));
+        assert!(!weak.is_alive());
+        assert!(weak.upgrade().is_none());
+        assert_eq!(try_instance::<i32>(id).unwrap_err(), InstanceErr::Freed(id));
+
+        // Handles taken before freeing stay usable.
+        *handle.bind_mut().unwrap() += 1;
+        assert_eq!(*handle.bind().unwrap(), 6);
+    }
+
+    #[test]
+    fn ids_are_not_reused() {
+        let first = register_instance("first");
+        free_instance(first);
+        let second = register_instance("second");
+
+        assert_ne!(first, second);
+        assert!(instance::<&str>(first).is_none());
+        assert_eq!(*instance::<&str>(second).unwrap().bind().unwrap(), "second");
+        free_instance(second);
+    }
+
+    /// Sets its flag when dropped.
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn last_instance_frees_ref_counted() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let first = register_ref_counted(DropFlag(dropped.clone()));
+        let weak = first.downgrade();
+
+        let second = weak.upgrade().unwrap();
+        let third = second.clone();
+        assert_eq!(first.strong_count(), Some(3));
+
+        drop(first);
+        drop(second);
+        assert_eq!(third.strong_count(), Some(1));
+        assert!(weak.is_alive());
+
+        drop(third);
+        assert!(weak.upgrade().is_none());
+        assert!(dropped.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn freeing_bound_instance_is_deferred() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let instance = register_ref_counted(DropFlag(dropped.clone()));
+        let weak = instance.downgrade();
+
+        let handle = GdHandle::clone(&instance);
+        let guard = handle.bind().unwrap();
+        drop(instance);
+        assert!(weak.upgrade().is_none());
+        assert!(!dropped.load(Ordering::Relaxed));
+
+        drop(guard);
+        drop(handle);
+        assert!(dropped.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,148 @@
+//! Counters of the borrows taken of every [`GdCell`](crate::GdCell), for performance tuning.
+//!
+//! Only available with the `stats` feature. Every cell counts its own borrows, and all cells additionally
+//! count into a process-wide total.
+
+use alloc::collections::BTreeMap;
+
+use crate::borrow_state::{BorrowState, BorrowStateErr};
+use crate::hooks::BorrowEventKind;
+use crate::lock::{DefaultLock, Lock};
+
+/// Counters of borrows taken and failed, as returned by [`GdCell::borrow_stats`](crate::GdCell::borrow_stats)
+/// and [`global_borrow_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BorrowStats {
+    /// The number of shared borrows taken, excluding upgradable ones.
+    pub shared: u64,
+    /// The number of upgradable borrows taken.
+    pub upgradable: u64,
+    /// The number of upgradable borrows upgraded into mutable borrows.
+    pub upgrades: u64,
+    /// The number of mutable borrows taken, excluding upgrades.
+    pub mutable: u64,
+    /// The number of mutable borrows marked as non-aliasing, which is how often reentrancy was allowed.
+    pub non_aliasing: u64,
+    /// The number of failed borrows, by the [name](BorrowStateErr::name) of the error they failed with.
+    pub failures: BTreeMap<&'static str, u64>,
+    /// The largest number of nested non-aliasing borrows reached.
+    pub max_depth: usize,
+}
+
+impl BorrowStats {
+    pub const fn new() -> Self {
+        Self {
+            shared: 0,
+            upgradable: 0,
+            upgrades: 0,
+            mutable: 0,
+            non_aliasing: 0,
+            failures: BTreeMap::new(),
+            max_depth: 0,
+        }
+    }
+
+    /// Returns the total number of failed borrows.
+    pub fn total_failures(&self) -> u64 {
+        self.failures.values().sum()
+    }
+
+    /// Count a transition which resulted in `state`.
+    pub(crate) fn record(
+        &mut self,
+        kind: BorrowEventKind,
+        state: &BorrowState,
+        error: Option<&BorrowStateErr>,
+    ) {
+        use BorrowEventKind as Kind;
+
+        if let Some(err) = error {
+            *self.failures.entry(err.name()).or_default() += 1;
+            return;
+        }
+
+        let counter = match kind {
+            Kind::Shared => &mut self.shared,
+            Kind::Upgradable => &mut self.upgradable,
+            Kind::Upgrade => &mut self.upgrades,
+            Kind::Mut => &mut self.mutable,
+            Kind::SetNonAliasing | Kind::SetNonAliasingReadOnly => &mut self.non_aliasing,
+            Kind::ReleaseShared
+            | Kind::ReleaseUpgradable
+            | Kind::ReleaseMut
+            | Kind::UnsetNonAliasing
+            | Kind::Poisoned => return,
+        };
+        *counter += 1;
+
+        self.max_depth = self.max_depth.max(state.non_aliasing_count());
+    }
+}
+
+static GLOBAL_STATS: DefaultLock<BorrowStats> = DefaultLock::new(BorrowStats::new());
+
+/// Count a transition of any cell in the process-wide counters.
+pub(crate) fn record_global(
+    kind: BorrowEventKind,
+    state: &BorrowState,
+    error: Option<&BorrowStateErr>,
+) {
+    GLOBAL_STATS.lock().record(kind, state, error);
+}
+
+/// Returns the borrows taken and failed of all cells since the process started, or since the last call to
+/// [`reset_global_borrow_stats`].
+pub fn global_borrow_stats() -> BorrowStats {
+    GLOBAL_STATS.lock().clone()
+}
+
+/// Reset the counters returned by [`global_borrow_stats`] to zero.
+pub fn reset_global_borrow_stats() {
+    *GLOBAL_STATS.lock() = BorrowStats::new();
+}
+
+#[cfg(test)]
+mod test {
+    use core::pin::pin;
+
+    use super::*;
+    use crate::GdCell;
+
+    #[test]
+    fn cell_counts_borrows_and_failures() {
+        let cell = pin!(GdCell::new(0));
+        let cell = cell.into_ref();
+
+        let mut guard = cell.gd_mut().unwrap();
+        assert!(cell.gd_ref().is_err());
+        assert!(cell.gd_mut().is_err());
+        let no_alias_guard = cell.set_non_aliasing(&mut *guard).unwrap();
+        let mut inner = cell.gd_mut().unwrap();
+        let inner_no_alias_guard = cell.set_non_aliasing(&mut *inner).unwrap();
+        drop(cell.gd_ref().unwrap());
+        drop(inner_no_alias_guard);
+        drop(inner);
+        drop(no_alias_guard);
+        drop(guard);
+
+        let stats = cell.borrow_stats();
+        assert_eq!(stats.shared, 1);
+        assert_eq!(stats.mutable, 2);
+        assert_eq!(stats.non_aliasing, 2);
+        assert_eq!(stats.max_depth, 2);
+        assert_eq!(stats.failures.get("HasAliasingRef"), Some(&2));
+        assert_eq!(stats.total_failures(), 2);
+
+        cell.reset_borrow_stats();
+        assert_eq!(cell.borrow_stats(), BorrowStats::new());
+
+        // Other tests run concurrently, so only check that this cell was counted.
+        let global = global_borrow_stats();
+        assert!(global.mutable >= 2);
+        assert!(global
+            .failures
+            .get("HasAliasingRef")
+            .is_some_and(|&n| n >= 2));
+    }
+}
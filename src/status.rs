@@ -0,0 +1,44 @@
+use crate::borrow_state::BorrowState;
+
+/// A snapshot of the borrows of a [`GdCell`](crate::GdCell), as returned by
+/// [`GdCell::borrow_status`](crate::GdCell::borrow_status).
+///
+/// This is a copy taken at the time of the call, it is not updated when the cell is borrowed further.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BorrowStatus {
+    /// The number of live shared borrows, including the upgradable one.
+    pub shared_count: usize,
+    /// The number of live mutable borrows.
+    pub mut_count: usize,
+    /// The number of live mutable borrows marked as non-aliasing.
+    pub non_aliasing_count: usize,
+    /// `true` if one of the shared borrows is upgradable.
+    pub upgradable: bool,
+    /// `true` if the innermost non-aliasing borrow only allows shared reentrant borrows.
+    pub readonly: bool,
+    /// `true` if the borrow state has reached an erroneous or unreliable state.
+    pub poisoned: bool,
+    /// The number of references that reentrant borrows are currently derived through, one for each
+    /// non-aliasing borrow.
+    pub reentrancy_depth: usize,
+}
+
+impl BorrowStatus {
+    pub(crate) fn new(state: &BorrowState) -> Self {
+        Self {
+            shared_count: state.shared_count(),
+            mut_count: state.mut_count(),
+            non_aliasing_count: state.non_aliasing_count(),
+            upgradable: state.has_upgradable(),
+            readonly: state.is_readonly(),
+            poisoned: state.is_poisoned(),
+            reentrancy_depth: state.non_aliasing_count(),
+        }
+    }
+
+    /// Returns `true` if there are any live borrows.
+    pub fn is_bound(&self) -> bool {
+        self.shared_count > 0 || self.mut_count > 0
+    }
+}
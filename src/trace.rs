@@ -0,0 +1,271 @@
+//! Traces of borrow operations, which can be replayed against a fresh [`BorrowState`] to reproduce a bug.
+//!
+//! A trace is written as its operations separated by whitespace, for instance
+//! `IncMut SetNoAlias IncShared DecShared UnsetNoAlias DecMut`.
+
+use alloc::vec::Vec;
+use core::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use crate::borrow_state::{BorrowState, BorrowStateErr};
+use crate::hooks::BorrowEventKind;
+
+/// A single operation on a [`BorrowState`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub enum BorrowOp {
+    /// [`BorrowState::increment_shared`].
+    IncShared,
+    /// [`BorrowState::decrement_shared`].
+    DecShared,
+    /// [`BorrowState::increment_mut`].
+    IncMut,
+    /// [`BorrowState::decrement_mut`].
+    DecMut,
+    /// [`BorrowState::set_non_aliasing`].
+    SetNoAlias,
+    /// [`BorrowState::set_non_aliasing_readonly`].
+    SetNoAliasReadOnly,
+    /// [`BorrowState::unset_non_aliasing`].
+    UnsetNoAlias,
+    /// [`BorrowState::increment_upgradable`].
+    IncUpgradable,
+    /// [`BorrowState::decrement_upgradable`].
+    DecUpgradable,
+    /// [`BorrowState::upgrade`].
+    Upgrade,
+}
+
+impl BorrowOp {
+    const ALL: [Self; 10] = [
+        Self::IncShared,
+        Self::DecShared,
+        Self::IncMut,
+        Self::DecMut,
+        Self::SetNoAlias,
+        Self::SetNoAliasReadOnly,
+        Self::UnsetNoAlias,
+        Self::IncUpgradable,
+        Self::DecUpgradable,
+        Self::Upgrade,
+    ];
+
+    /// Apply this operation to `state`, returning what the corresponding [`BorrowState`] method returns.
+    pub fn apply(self, state: &mut BorrowState) -> Result<usize, BorrowStateErr> {
+        match self {
+            Self::IncShared => state.increment_shared(),
+            Self::DecShared => state.decrement_shared(),
+            Self::IncMut => state.increment_mut(),
+            Self::DecMut => state.decrement_mut(),
+            Self::SetNoAlias => state.set_non_aliasing(),
+            Self::SetNoAliasReadOnly => state.set_non_aliasing_readonly(),
+            Self::UnsetNoAlias => state.unset_non_aliasing(),
+            Self::IncUpgradable => state.increment_upgradable(),
+            Self::DecUpgradable => state.decrement_upgradable(),
+            Self::Upgrade => state.upgrade(),
+        }
+    }
+
+    /// Returns the operation a borrow transition of a [`GdCell`](crate::GdCell) performed, if any.
+    #[cfg_attr(not(feature = "history"), allow(dead_code))]
+    pub(crate) fn from_event(kind: BorrowEventKind) -> Option<Self> {
+        use BorrowEventKind as Kind;
+
+        match kind {
+            Kind::Shared => Some(Self::IncShared),
+            Kind::ReleaseShared => Some(Self::DecShared),
+            Kind::Upgradable => Some(Self::IncUpgradable),
+            Kind::ReleaseUpgradable => Some(Self::DecUpgradable),
+            Kind::Upgrade => Some(Self::Upgrade),
+            Kind::Mut => Some(Self::IncMut),
+            Kind::ReleaseMut => Some(Self::DecMut),
+            Kind::SetNonAliasing => Some(Self::SetNoAlias),
+            Kind::SetNonAliasingReadOnly => Some(Self::SetNoAliasReadOnly),
+            Kind::UnsetNonAliasing => Some(Self::UnsetNoAlias),
+            Kind::Poisoned => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::IncShared => "IncShared",
+            Self::DecShared => "DecShared",
+            Self::IncMut => "IncMut",
+            Self::DecMut => "DecMut",
+            Self::SetNoAlias => "SetNoAlias",
+            Self::SetNoAliasReadOnly => "SetNoAliasReadOnly",
+            Self::UnsetNoAlias => "UnsetNoAlias",
+            Self::IncUpgradable => "IncUpgradable",
+            Self::DecUpgradable => "DecUpgradable",
+            Self::Upgrade => "Upgrade",
+        }
+    }
+}
+
+impl fmt::Display for BorrowOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for BorrowOp {
+    type Err = ParseTraceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|op| op.name() == s)
+            .ok_or_else(|| ParseTraceError(s.into()))
+    }
+}
+
+/// Error returned when parsing a [`BorrowOp`] or [`BorrowTrace`] fails.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("unknown borrow operation `{0}`")]
+pub struct ParseTraceError(alloc::string::String);
+
+/// A sequence of [`BorrowOp`]s, in the order they were performed.
+///
+/// With the `history` feature, traces of a live cell can be recorded with `GdCell::borrow_trace`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BorrowTrace {
+    /// The operations, from first to last.
+    pub ops: Vec<BorrowOp>,
+}
+
+impl BorrowTrace {
+    /// Apply every operation in order to `state`, returning the result of each operation.
+    ///
+    /// Failed operations are applied too, since a failure can poison the state.
+    pub fn replay(&self, state: &mut BorrowState) -> Vec<Result<usize, BorrowStateErr>> {
+        self.ops.iter().map(|op| op.apply(state)).collect()
+    }
+}
+
+impl FromIterator<BorrowOp> for BorrowTrace {
+    fn from_iter<I: IntoIterator<Item = BorrowOp>>(iter: I) -> Self {
+        Self {
+            ops: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl fmt::Display for BorrowTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, op) in self.ops.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{op}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for BorrowTrace {
+    type Err = ParseTraceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_whitespace().map(BorrowOp::from_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn trace_roundtrips_through_text() {
+        let text = "IncMut SetNoAlias IncShared DecShared UnsetNoAlias DecMut";
+        let trace = text.parse::<BorrowTrace>().unwrap();
+
+        assert_eq!(trace.ops.len(), 6);
+        assert_eq!(trace.to_string(), text);
+        assert_eq!(
+            "IncShared\n  DecShared".parse::<BorrowTrace>().unwrap().ops,
+            [BorrowOp::IncShared, BorrowOp::DecShared]
+        );
+        assert_eq!(
+            "IncShared Bogus".parse::<BorrowTrace>(),
+            Err(ParseTraceError("Bogus".into()))
+        );
+    }
+
+    #[test]
+    fn replay_reproduces_errors() {
+        let trace = "IncShared IncMut DecShared IncMut"
+            .parse::<BorrowTrace>()
+            .unwrap();
+        let mut state = BorrowState::new();
+
+        let results = trace.replay(&mut state);
+        assert_eq!(
+            results,
+            [Ok(1), Err(BorrowStateErr::HasSharedRef), Ok(0), Ok(1)]
+        );
+        assert_eq!(state.mut_count(), 1);
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn trace_recorded_from_cell_replays() {
+        use core::pin::pin;
+
+        use crate::GdCell;
+
+        let cell = pin!(GdCell::new(0));
+        let cell = cell.into_ref();
+        cell.enable_history(16);
+
+        let mut guard = cell.gd_mut().unwrap();
+        assert!(cell.gd_ref().is_err());
+        let no_alias_guard = cell.set_non_aliasing_readonly(&mut *guard).unwrap();
+        assert!(cell.gd_mut().is_err());
+        drop(cell.gd_ref().unwrap());
+        drop(no_alias_guard);
+
+        let trace = cell.borrow_trace();
+        assert_eq!(
+            trace.to_string(),
+            "IncMut IncShared SetNoAliasReadOnly IncMut IncShared DecShared UnsetNoAlias"
+        );
+
+        let mut state = BorrowState::with_policy(cell.policy());
+        let results = trace.replay(&mut state);
+        let failed = results.iter().map(Result::is_err).collect::<Vec<_>>();
+        assert_eq!(failed, [false, true, false, true, false, false, false]);
+        assert_eq!(state.mut_count(), cell.borrow_status().mut_count);
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn trace_leaves_out_failures_outside_borrow_state() {
+        use core::pin::pin;
+
+        use crate::GdCell;
+
+        let cell = pin!(GdCell::<_, 1>::new_with_depth(0));
+        let cell = cell.into_ref();
+        cell.enable_history(16);
+
+        let mut guard = cell.gd_mut().unwrap();
+        let _no_alias_guard = cell.set_non_aliasing(&mut *guard).unwrap();
+        let mut inner = cell.gd_mut().unwrap();
+        // Fails because the pointer stack is full, which replaying against a `BorrowState` would not.
+        assert!(cell.set_non_aliasing(&mut *inner).is_err());
+
+        let trace = cell.borrow_trace();
+        assert_eq!(trace.to_string(), "IncMut SetNoAlias IncMut");
+
+        let mut state = BorrowState::with_policy(cell.policy());
+        assert!(trace.replay(&mut state).iter().all(Result::is_ok));
+        assert_eq!(
+            state.non_aliasing_count(),
+            cell.borrow_status().reentrancy_depth
+        );
+    }
+}